@@ -1,26 +1,155 @@
 use std::fs;
 
-pub fn run(part: u8, is_test: bool) {
+use crate::utils::cli::{flag_value, has_flag};
+
+/// Safe dial with `size` numbers, starting at `start`, whose password counts visits of `mark`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Dial {
+    size: i64,
+    start: i64,
+    mark: i64,
+}
+
+impl Default for Dial {
+    fn default() -> Self {
+        Dial { size: 100, start: 50, mark: 0 }
+    }
+}
+
+/// Outcome of a single rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Step {
+    rotation: i64,
+    position: i64,
+    /// Clicks that left the dial on the mark during this rotation, the last one included.
+    mark_clicks: u64,
+}
+
+impl Dial {
+    fn new(size: i64, start: i64, mark: i64) -> Result<Self, String> {
+        if size <= 0 {
+            return Err(format!("Dial size must be positive, got {}", size));
+        }
+        for (name, value) in [("Start", start), ("Mark", mark)] {
+            if !(0..size).contains(&value) {
+                return Err(format!(
+                    "{} position {} is not on a dial of size {}",
+                    name, value, size
+                ));
+            }
+        }
+        Ok(Dial { size, start, mark })
+    }
+
+    /// Number of clicks that leave the dial on the mark while turning it by `rotation` from
+    /// `position`. Works in `i128` so that any `i64` rotation on any dial size fits.
+    fn mark_clicks(&self, position: i64, rotation: i64) -> u64 {
+        let (size, rotation) = (self.size as i128, rotation as i128);
+        let offset = (position as i128 - self.mark as i128).rem_euclid(size);
+        let clicks = if rotation >= 0 {
+            (offset + rotation) / size
+        } else {
+            // Turning left from the mark, the next visit is a full turn away
+            let first = if offset == 0 { size } else { offset };
+            if -rotation >= first { (-rotation - first) / size + 1 } else { 0 }
+        };
+        clicks as u64
+    }
+
+    fn turn(&self, position: i64, rotation: i64) -> i64 {
+        (position as i128 + rotation as i128).rem_euclid(self.size as i128) as i64
+    }
+
+    fn replay(&self, rotations: &[i64]) -> Vec<Step> {
+        let mut position = self.start;
+        rotations
+            .iter()
+            .map(|&rotation| {
+                let mark_clicks = self.mark_clicks(position, rotation);
+                position = self.turn(position, rotation);
+                Step { rotation, position, mark_clicks }
+            })
+            .collect()
+    }
+}
+
+fn print_trace(dial: &Dial, trace: &[Step]) {
+    let mut position = dial.start;
+    for step in trace {
+        let direction = if step.rotation < 0 { 'L' } else { 'R' };
+        println!(
+            "[{}{}]: {} -> {} ({} clicks on {})",
+            direction,
+            step.rotation.abs(),
+            position,
+            step.position,
+            step.mark_clicks,
+            dial.mark
+        );
+        position = step.position;
+    }
+}
+
+fn parse_dial(args: &[String]) -> Result<Dial, String> {
+    let default = Dial::default();
+    Dial::new(
+        flag_value(args, "--dial-size")?.unwrap_or(default.size),
+        flag_value(args, "--start")?.unwrap_or(default.start),
+        flag_value(args, "--mark")?.unwrap_or(default.mark),
+    )
+}
+
+/// Parses a rotation such as `L68` or `r14` into a signed click count, right being positive.
+/// Distances go up to `i64::MAX`, which the dial arithmetic handles without overflowing.
+fn parse_rotation(text: &str) -> Result<i64, String> {
+    let mut chars = text.chars();
+    let direction = chars.next().ok_or("missing direction")?;
+    let distance = chars.as_str().trim();
+    let clicks = distance
+        .parse::<u64>()
+        .ok()
+        .and_then(|clicks| i64::try_from(clicks).ok())
+        .ok_or(format!("invalid distance '{}'", distance))?;
+    match direction.to_ascii_uppercase() {
+        'R' => Ok(clicks),
+        'L' => Ok(-clicks),
+        _ => Err(format!("unknown direction '{}'", direction)),
+    }
+}
+
+/// Parses one rotation per line, ignoring blank lines and surrounding whitespace.
+fn parse_rotations(input: &str) -> Result<Vec<i64>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            parse_rotation(line.trim())
+                .map_err(|err| format!("Line {} ({}): {}", idx + 1, line.trim(), err))
+        })
+        .collect()
+}
+
+pub fn run(part: u8, is_test: bool, args: &[String]) {
     let input_file = if is_test { "test_input.txt" } else { "input.txt" };
     let input = fs::read_to_string(format!("src/day_01/{}", input_file))
         .expect("Failed to read input file");
 
-    let rotations: Vec<i32> = input
-        .lines()
-        .map(|line| {
-            let (rotation_str, number_str) = line.split_at(1);
-            let rotation = rotation_str.chars().next().expect("Invalid rotation");
-            let number = number_str.parse::<i32>().expect("Invalid number");
-            match rotation {
-                'R' => number,
-                'L' => -number,
-                _ => panic!(),
-            }
-        })
-        .collect();
+    let options = parse_rotations(&input).and_then(|rotations| Ok((rotations, parse_dial(args)?)));
+    let (rotations, dial) = match options {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        },
+    };
+    if has_flag(args, "--trace") {
+        print_trace(&dial, &dial.replay(&rotations));
+    }
 
     let result = match part {
-        1 => part1(rotations.clone()),
+        1 => part1(&dial, &rotations),
+        2 => part2(&dial, &rotations),
         _ => {
             println!("Part {} not implemented for day 1", part);
             return;
@@ -30,34 +159,149 @@ pub fn run(part: u8, is_test: bool) {
     println!("Day 1 Part {}: {}", part, result);
 }
 
-fn part1(rotations: Vec<i32>) -> i32 {
-    let starts_at: i32 = 50;
-
-    let mut count: i32 = 0;
-    std::iter::once(starts_at).chain(rotations).reduce(|a, b| {
-        let result = (a + b).rem_euclid(100);
-        if result == 0 {
-            count += 1;
-        }
-        result
-    });
+fn part1(dial: &Dial, rotations: &[i64]) -> u64 {
+    dial.replay(rotations).iter().filter(|step| step.position == dial.mark).count() as u64
+}
 
-    count
+fn part2(dial: &Dial, rotations: &[i64]) -> u64 {
+    dial.replay(rotations)
+        .iter()
+        .try_fold(0u64, |total, step| total.checked_add(step.mark_clicks))
+        .expect("Click count overflows a u64")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::rng::Lcg;
 
     #[test]
     fn test_simple() {
         let rotations = vec![1, 5, -56];
-        assert_eq!(part1(rotations), 1);
+        assert_eq!(part1(&Dial::default(), &rotations), 1);
     }
 
     #[test]
     fn test_example() {
         let rotations = vec![-68, -30, 48, -5, 60, -55, -1, -99, 14, -82];
-        assert_eq!(part1(rotations), 3);
+        assert_eq!(part1(&Dial::default(), &rotations), 3);
+    }
+
+    fn simulate_clicks(dial: &Dial, rotations: &[i64]) -> u64 {
+        let mut position = dial.start;
+        let mut count = 0;
+        for &rotation in rotations {
+            for _ in 0..rotation.abs() {
+                position = (position + rotation.signum()).rem_euclid(dial.size);
+                if position == dial.mark {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_example_part_2() {
+        let rotations = vec![-68, -30, 48, -5, 60, -55, -1, -99, 14, -82];
+        assert_eq!(part2(&Dial::default(), &rotations), 6);
+    }
+
+    #[test]
+    fn test_multi_turn_part_2() {
+        assert_eq!(part2(&Dial::default(), &[1000]), 10);
+        assert_eq!(part2(&Dial::default(), &[-1000]), 10);
+        assert_eq!(part2(&Dial::default(), &[-50, -100, 100, -1, 1]), 4);
+    }
+
+    #[test]
+    fn test_part_2_matches_simulation() {
+        // Deterministic pseudo-random rotations, both short and multi-turn
+        let mut rng = Lcg::new(12345);
+        let rotations: Vec<i64> = (0..500)
+            .map(|_| {
+                let magnitude = rng.below(450) as i64;
+                if rng.below(2) == 0 { magnitude } else { -magnitude }
+            })
+            .collect();
+        assert_eq!(
+            part2(&Dial::default(), &rotations),
+            simulate_clicks(&Dial::default(), &rotations)
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let trace = Dial::default().replay(&[-68, -30, 48]);
+        assert_eq!(
+            trace,
+            vec![
+                Step { rotation: -68, position: 82, mark_clicks: 1 },
+                Step { rotation: -30, position: 52, mark_clicks: 0 },
+                Step { rotation: 48, position: 0, mark_clicks: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_dial() {
+        let dial = Dial::new(10, 3, 7).unwrap();
+        let rotations = [4, -14, 25, -1, -9, 10];
+        assert_eq!(part2(&dial, &rotations), simulate_clicks(&dial, &rotations));
+        // 3 -> 7 -> 3 -> 8 -> 7 -> 8 -> 8
+        assert_eq!(part1(&dial, &rotations), 2);
+
+        assert!(Dial::new(0, 0, 0).is_err());
+        assert_eq!(
+            Dial::new(10, 10, 0),
+            Err("Start position 10 is not on a dial of size 10".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_rotations() {
+        assert_eq!(parse_rotations("L68\n  r14 \n\nl5\nR 3\n"), Ok(vec![-68, 14, -5, 3]));
+        assert_eq!(parse_rotations(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_parse_rotations_errors() {
+        assert_eq!(
+            parse_rotations("L1\nX12"),
+            Err("Line 2 (X12): unknown direction 'X'".to_string())
+        );
+        assert_eq!(parse_rotations("\nR"), Err("Line 2 (R): invalid distance ''".to_string()));
+        assert_eq!(
+            parse_rotations("R1\nL-4"),
+            Err("Line 2 (L-4): invalid distance '-4'".to_string())
+        );
+        assert_eq!(parse_rotations("é5"), Err("Line 1 (é5): unknown direction 'é'".to_string()));
+        assert_eq!(
+            parse_rotations("R1\nL9223372036854775808"),
+            Err("Line 2 (L9223372036854775808): invalid distance '9223372036854775808'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_huge_rotations() {
+        let rotations = parse_rotations("R2147483647\nL9223372036854775807\nR4294967296").unwrap();
+        assert_eq!(rotations, vec![i32::MAX as i64, -i64::MAX, 1 << 32]);
+        assert_eq!(
+            part2(&Dial::default(), &rotations),
+            21_474_836 + 92_233_720_368_547_758 + 42_949_673
+        );
+    }
+
+    #[test]
+    fn test_huge_rotations() {
+        let dial = Dial::default();
+        // 50 + 2^31 - 1 lands on 97 after passing 0 once per hundred clicks
+        assert_eq!(part2(&dial, &[i32::MAX as i64]), 21_474_836);
+        assert_eq!(dial.replay(&[i32::MAX as i64])[0].position, 97);
+        let rotations = [i64::MAX, -i64::MAX, i64::MAX];
+        assert_eq!(part2(&dial, &rotations), 3 * (i64::MAX as u64 / 100));
+
+        let wide = Dial::new(i64::MAX, i64::MAX - 1, 0).unwrap();
+        assert_eq!(part2(&wide, &[i64::MAX, i64::MAX]), 2);
     }
 }
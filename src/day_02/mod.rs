@@ -1,73 +1,303 @@
-use std::fs;
+use std::{
+    fs,
+    iter::Sum,
+    ops::{Add, Sub},
+    str::FromStr,
+};
 
-pub fn run(part: u8, is_test: bool) {
+use crate::utils::cli::{flag_value, has_flag};
+
+/// Which repetitions make an ID invalid. Counts are numbers of copies of the block (at least 2)
+/// and periods are block lengths in digits.
+#[derive(Debug, Clone, PartialEq)]
+enum RepetitionRule {
+    Exactly(u32),
+    AtLeast(u32),
+    AtMost(u32),
+    Periods(Vec<u32>),
+}
+
+impl RepetitionRule {
+    /// Whether a `length`-digit number whose shortest repeating block is `period` digits long
+    /// breaks the rule. Such a number is a block repeated `k` times exactly when `k` divides
+    /// `length / period`.
+    fn matches(&self, length: u32, period: u32) -> bool {
+        let copies = length / period;
+        let repeats = |k: u32| k >= 2 && copies.is_multiple_of(k);
+        match self {
+            RepetitionRule::Exactly(k) => repeats(*k),
+            RepetitionRule::AtLeast(k) => (*k..=copies).any(repeats),
+            RepetitionRule::AtMost(k) => (2..=*k).any(repeats),
+            RepetitionRule::Periods(periods) => periods.iter().any(|&p| {
+                p > 0 && p < length && length.is_multiple_of(p) && p.is_multiple_of(period)
+            }),
+        }
+    }
+}
+
+impl FromStr for RepetitionRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage =
+            "Unknown rule, expected exactly:<k>, at-least:<k>, at-most:<k> or periods:<p,...>";
+        let (kind, value) = s.split_once(':').ok_or(usage)?;
+        let number = |text: &str| {
+            text.trim().parse::<u32>().map_err(|err| format!("Invalid number '{}': {}", text, err))
+        };
+        let count = |text: &str| match number(text)? {
+            k if k < 2 => Err(format!("Repetition count must be at least 2, got {}", k)),
+            k => Ok(k),
+        };
+        match kind {
+            "exactly" => Ok(RepetitionRule::Exactly(count(value)?)),
+            "at-least" => Ok(RepetitionRule::AtLeast(count(value)?)),
+            "at-most" => Ok(RepetitionRule::AtMost(count(value)?)),
+            "periods" => {
+                Ok(RepetitionRule::Periods(value.split(',').map(number).collect::<Result<_, _>>()?))
+            },
+            _ => Err(usage.to_string()),
+        }
+    }
+}
+
+/// Number and sum of the invalid IDs found.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Tally {
+    count: u128,
+    sum: u128,
+}
+
+impl Add for Tally {
+    type Output = Tally;
+
+    fn add(self, other: Tally) -> Tally {
+        Tally { count: self.count + other.count, sum: self.sum + other.sum }
+    }
+}
+
+impl Sub for Tally {
+    type Output = Tally;
+
+    fn sub(self, other: Tally) -> Tally {
+        Tally { count: self.count - other.count, sum: self.sum - other.sum }
+    }
+}
+
+impl Sum for Tally {
+    fn sum<I: Iterator<Item = Tally>>(iter: I) -> Tally {
+        iter.fold(Tally::default(), Add::add)
+    }
+}
+
+pub fn run(part: u8, is_test: bool, args: &[String]) {
     let input_file = if is_test { "test_input.txt" } else { "input.txt" };
     let input = fs::read_to_string(format!("src/day_02/{}", input_file))
         .expect("Failed to read input file");
 
-    // Parse input here
-    let lines: Vec<(u64, u64)> = input
-        .split(",")
-        .map(|range| match range.split_once('-') {
-            Some((start, end)) => (start.trim().parse().unwrap(), end.trim().parse().unwrap()),
-            None => (0, 0),
-        })
-        .collect();
-
-    dbg!(&lines);
+    let options = parse_ranges(&input).and_then(|ranges| Ok((ranges, parse_options(args)?)));
+    let (mut lines, (rule, radix)) = match options {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        },
+    };
+    if has_flag(args, "--merge") {
+        lines = merge_ranges(lines);
+    }
 
-    let result = match part {
-        1 => part1(&lines),
-        2 => part2(&lines),
+    let result = match (part, rule) {
+        (1 | 2, Some(rule)) => tally(&lines, &rule, radix),
+        (1, None) => part1(&lines, radix),
+        (2, None) => part2(&lines, radix),
         _ => {
             println!("Part {} not implemented for day 2", part);
             return;
         },
     };
+    if has_flag(args, "--count") {
+        println!("Invalid IDs: {}", result.count);
+    }
+    println!("Day 2 Part {}: {}", part, result.sum);
+}
 
-    println!("Day 2 Part {}: {}", part, result);
+/// Parses a range such as `11-22` into its inclusive bounds.
+fn parse_range(text: &str) -> Result<(u64, u64), String> {
+    let (start, end) = text.split_once('-').ok_or("missing '-'")?;
+    let bound = |bound: &str| {
+        bound.trim().parse::<u64>().map_err(|_| format!("invalid bound '{}'", bound.trim()))
+    };
+    let (start, end) = (bound(start)?, bound(end)?);
+    if start > end {
+        return Err(format!("start {} is after end {}", start, end));
+    }
+    Ok((start, end))
 }
 
-fn is_repeating_twice(number: u64) -> bool {
-    let string = number.to_string();
-    if string.len().rem_euclid(2) != 0 {
-        return false;
+/// Parses the comma-separated ranges, tolerating whitespace and line breaks between them and a
+/// trailing comma.
+fn parse_ranges(input: &str) -> Result<Vec<(u64, u64)>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(Vec::new());
     }
-    let (left, right) = string.split_at(string.len() / 2);
-    left == right
+    input
+        .strip_suffix(',')
+        .unwrap_or(input)
+        .split(',')
+        .enumerate()
+        .map(|(idx, range)| {
+            parse_range(range.trim())
+                .map_err(|err| format!("Range {} ({}): {}", idx + 1, range.trim(), err))
+        })
+        .collect()
 }
 
-fn is_repeating(number: u64) -> bool {
-    let string = number.to_string();
-    for len in 1..string.len() {
-        if string.len().rem_euclid(len) == 0 {
-            let first_part = &string[..len];
-            let all_equal =
-                string.as_bytes().chunks(len).all(|chunk| chunk == first_part.as_bytes());
-            if all_equal {
-                return true;
-            }
+/// Sorts the ranges and merges those that overlap or touch, so that no ID is counted twice.
+fn merge_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
         }
     }
-    false
+    merged
+}
+
+/// Rule overriding the part's own, and the radix IDs are written in.
+fn parse_options(args: &[String]) -> Result<(Option<RepetitionRule>, u64), String> {
+    let radix = flag_value(args, "--radix")?.unwrap_or(10);
+    if radix < 2 {
+        return Err(format!("Radix must be at least 2, got {}", radix));
+    }
+    Ok((flag_value(args, "--rule")?, radix))
+}
+
+/// Number of digits of `number` in base `radix`.
+fn digit_count(number: u64, radix: u64) -> u32 {
+    number.checked_ilog(radix).unwrap_or(0) + 1
+}
+
+/// Invalid IDs among the `length`-digit numbers in `[start, end]` made of a `period`-digit block
+/// repeated `length / period` times.
+///
+/// Such numbers are exactly `block * multiplier` with `multiplier = 1 + radix^period + ...`, so
+/// the matching blocks form a contiguous range whose sum has a closed form.
+fn periodic_tally(start: u64, end: u64, length: u32, period: u32, radix: u64) -> Tally {
+    let radix = radix as u128;
+    let multiplier: u128 = (0..length / period).map(|i| radix.pow(i * period)).sum();
+    let (start, end) = (start as u128, end as u128);
+
+    let first = start.div_ceil(multiplier).max(radix.pow(period - 1));
+    let last = (end / multiplier).min(radix.pow(period) - 1);
+    if first > last {
+        return Tally::default();
+    }
+    let count = last - first + 1;
+    Tally { count, sum: multiplier * (first + last) * count / 2 }
+}
+
+/// Invalid IDs among the `length`-digit numbers in `[start, end]`.
+///
+/// A number repeating a `period`-digit block also repeats any longer block whose size is a
+/// multiple of `period`, so tallies are taken per shortest period: the tally for `period` minus
+/// the tallies already attributed to its own divisors. Each shortest period is then kept or
+/// dropped as a whole by the rule.
+fn length_tally(start: u64, end: u64, length: u32, rule: &RepetitionRule, radix: u64) -> Tally {
+    let periods: Vec<u32> = (1..length).filter(|period| length.is_multiple_of(*period)).collect();
+    let mut minimal: Vec<Tally> = Vec::with_capacity(periods.len());
+    for (idx, &period) in periods.iter().enumerate() {
+        let overcounted: Tally = periods[..idx]
+            .iter()
+            .zip(&minimal)
+            .filter(|&(&divisor, _)| period.is_multiple_of(divisor))
+            .map(|(_, &tally)| tally)
+            .sum();
+        minimal.push(periodic_tally(start, end, length, period, radix) - overcounted);
+    }
+    periods
+        .iter()
+        .zip(minimal)
+        .filter(|&(&period, _)| rule.matches(length, period))
+        .map(|(_, tally)| tally)
+        .sum()
+}
+
+/// Splits `[start, end]` into sub-ranges whose numbers all have the same digit count.
+fn by_length(start: u64, end: u64, radix: u64) -> impl Iterator<Item = (u64, u64, u32)> {
+    (digit_count(start, radix)..=digit_count(end, radix)).map(move |length| {
+        let low = if length == 1 { 0 } else { radix.pow(length - 1) };
+        let high = radix.checked_pow(length).map_or(u64::MAX, |p| p - 1);
+        (start.max(low), end.min(high), length)
+    })
+}
+
+fn tally(ranges: &[(u64, u64)], rule: &RepetitionRule, radix: u64) -> Tally {
+    ranges
+        .iter()
+        .flat_map(|&(start, end)| by_length(start, end, radix))
+        .map(|(start, end, length)| length_tally(start, end, length, rule, radix))
+        .sum()
 }
 
-fn part1(ranges: &[(u64, u64)]) -> u64 {
-    ranges.iter().flat_map(|&(start, end)| start..=end).filter(|&n| is_repeating_twice(n)).sum()
+fn part1(ranges: &[(u64, u64)], radix: u64) -> Tally {
+    tally(ranges, &RepetitionRule::Exactly(2), radix)
 }
 
-fn part2(ranges: &[(u64, u64)]) -> u64 {
-    ranges.iter().flat_map(|&(start, end)| start..=end).filter(|&n| is_repeating(n)).sum()
+fn part2(ranges: &[(u64, u64)], radix: u64) -> Tally {
+    tally(ranges, &RepetitionRule::AtLeast(2), radix)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Every `k >= 2` such that `number` is a block repeated `k` times in base `radix`.
+    fn repetitions(number: u64, radix: u64) -> Vec<u32> {
+        let mut digits = Vec::new();
+        let mut rest = number;
+        loop {
+            digits.push(rest % radix);
+            rest /= radix;
+            if rest == 0 {
+                break;
+            }
+        }
+        let length = digits.len();
+        (2..=length)
+            .filter(|&k| {
+                length.is_multiple_of(k)
+                    && digits.chunks(length / k).all(|chunk| chunk == &digits[..length / k])
+            })
+            .map(|k| k as u32)
+            .collect()
+    }
+
+    fn brute_force(start: u64, end: u64, rule: &RepetitionRule, radix: u64) -> Tally {
+        (start..=end)
+            .filter(|&number| {
+                let reps = repetitions(number, radix);
+                let length = digit_count(number, radix);
+                match rule {
+                    RepetitionRule::Exactly(k) => reps.contains(k),
+                    RepetitionRule::AtLeast(k) => reps.iter().any(|r| r >= k),
+                    RepetitionRule::AtMost(k) => reps.iter().any(|r| r <= k),
+                    RepetitionRule::Periods(periods) => {
+                        reps.iter().any(|r| periods.contains(&(length / r)))
+                    },
+                }
+            })
+            .map(|number| Tally { count: 1, sum: number as u128 })
+            .sum()
+    }
+
     #[test]
     fn test_simple() {
         let ranges = vec![(11, 22)];
-        assert_eq!(part1(&ranges), 33);
+        assert_eq!(part1(&ranges, 10).sum, 33);
     }
 
     #[test]
@@ -82,19 +312,19 @@ mod tests {
             (446443, 446449),
             (38593856, 38593862),
         ];
-        assert_eq!(part1(&ranges), 1227775554);
+        assert_eq!(part1(&ranges, 10).sum, 1227775554);
     }
 
     #[test]
     fn test_simple_part_2() {
         let ranges = vec![(11, 22)];
-        assert_eq!(part2(&ranges), 33);
+        assert_eq!(part2(&ranges, 10).sum, 33);
     }
 
     #[test]
     fn test_simple_2_part_2() {
         let ranges = vec![(2121212118, 2121212124)];
-        assert_eq!(part2(&ranges), 2121212121);
+        assert_eq!(part2(&ranges, 10).sum, 2121212121);
     }
 
     #[test]
@@ -115,8 +345,101 @@ mod tests {
         let ranges: Vec<(u64, u64)> =
             ranges_extended.iter().map(|(start, end, _)| (*start, *end)).collect();
         for (start, end, total) in ranges_extended {
-            assert_eq!(part2(&[(start, end)]), total);
+            assert_eq!(part2(&[(start, end)], 10).sum, total);
+        }
+        assert_eq!(part2(&ranges, 10).sum, 4174379265);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        for (start, end) in [(0, 12_345), (95, 115), (99_990, 1_000_020), (7_777_000, 7_778_000)] {
+            let twice = brute_force(start, end, &RepetitionRule::Exactly(2), 10);
+            let repeated = brute_force(start, end, &RepetitionRule::AtLeast(2), 10);
+            assert_eq!(part1(&[(start, end)], 10), twice);
+            assert_eq!(part2(&[(start, end)], 10), repeated);
         }
-        assert_eq!(part2(&ranges), 4174379265);
+    }
+
+    #[test]
+    fn test_rules_and_radixes() {
+        let rules = [
+            RepetitionRule::Exactly(2),
+            RepetitionRule::Exactly(3),
+            RepetitionRule::AtLeast(3),
+            RepetitionRule::AtMost(3),
+            RepetitionRule::Periods(vec![1]),
+            RepetitionRule::Periods(vec![2, 3]),
+        ];
+        for radix in [2, 3, 10, 16] {
+            for rule in &rules {
+                for (start, end) in [(0, 5_000), (65_000, 70_000)] {
+                    assert_eq!(
+                        tally(&[(start, end)], rule, radix),
+                        brute_force(start, end, rule, radix),
+                        "{:?} in base {} over {}-{}",
+                        rule,
+                        radix,
+                        start,
+                        end
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!("exactly:3".parse(), Ok(RepetitionRule::Exactly(3)));
+        assert_eq!("at-most:4".parse(), Ok(RepetitionRule::AtMost(4)));
+        assert_eq!("periods:1,2".parse(), Ok(RepetitionRule::Periods(vec![1, 2])));
+        assert!("at-least:1".parse::<RepetitionRule>().is_err());
+        assert!("twice".parse::<RepetitionRule>().is_err());
+    }
+
+    #[test]
+    fn test_huge_ranges() {
+        // Every 2-digit block repeated twice: 1111 + 1212 + ... + 9999 = 101 * (10 + ... + 99)
+        assert_eq!(part1(&[(1000, 9999)], 10).sum, 101 * 4905);
+        // The whole u64 domain is summed exactly, without overflowing
+        assert!(part2(&[(0, u64::MAX)], 10).sum > u64::MAX as u128);
+        assert_eq!(part2(&[(u64::MAX - 10, u64::MAX)], 10).sum, 0);
+        // 0xFFFF_FFFF_FFFF_FFFF is every hexadecimal period at once
+        let all_f = tally(&[(u64::MAX - 10, u64::MAX)], &RepetitionRule::AtLeast(2), 16);
+        assert_eq!(all_f, Tally { count: 1, sum: u64::MAX as u128 });
+        // 20-digit doubles stop at block 1844674407, the largest one fitting in a u64
+        let (first, last) = (1_000_000_000u128, 1_844_674_407u128);
+        assert_eq!(
+            part1(&[(10_000_000_000_000_000_000, u64::MAX)], 10).sum,
+            10_000_000_001 * (first + last) * (last - first + 1) / 2
+        );
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(parse_ranges("11-22,95-115\n"), Ok(vec![(11, 22), (95, 115)]));
+        assert_eq!(parse_ranges("11-22,\n 95 - 115,\n"), Ok(vec![(11, 22), (95, 115)]));
+        assert_eq!(parse_ranges("\n"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_parse_ranges_errors() {
+        assert_eq!(parse_ranges("11-22,42"), Err("Range 2 (42): missing '-'".to_string()));
+        assert_eq!(parse_ranges("11-22,,95-115"), Err("Range 2 (): missing '-'".to_string()));
+        assert_eq!(parse_ranges("1-x,11-22"), Err("Range 1 (1-x): invalid bound 'x'".to_string()));
+        assert_eq!(
+            parse_ranges("11-22,5-3"),
+            Err("Range 2 (5-3): start 5 is after end 3".to_string())
+        );
+        assert!(parse_ranges("1-99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let ranges =
+            vec![(95, 115), (11, 22), (20, 30), (31, 40), (100, 101), (u64::MAX, u64::MAX)];
+        let merged = merge_ranges(ranges.clone());
+        assert_eq!(merged, vec![(11, 40), (95, 115), (u64::MAX, u64::MAX)]);
+        // 22 is in two of the original ranges but only counted once after merging
+        assert_eq!(part1(&ranges, 10).sum, part1(&merged, 10).sum + 22);
     }
 }
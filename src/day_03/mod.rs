@@ -1,6 +1,14 @@
-use std::{cmp::Ordering, fs};
+use std::fs;
 
-pub fn run(part: u8, is_test: bool) {
+use crate::utils::{
+    bigint::BigUint,
+    cli::{flag_value, has_flag},
+};
+
+const PART_1_BATTERIES: usize = 2;
+const PART_2_BATTERIES: usize = 12;
+
+pub fn run(part: u8, is_test: bool, args: &[String]) {
     let input_file = if is_test { "test_input.txt" } else { "input.txt" };
     let input = fs::read_to_string(format!("src/day_03/{}", input_file))
         .expect("Failed to read input file");
@@ -8,53 +16,133 @@ pub fn run(part: u8, is_test: bool) {
     // Parse input here
     let banks: Vec<&str> = input.lines().collect();
 
-    let result = match part {
-        1 => part1(&banks),
-        2 => part2(&banks),
+    let default = match part {
+        1 => PART_1_BATTERIES,
+        2 => PART_2_BATTERIES,
         _ => {
             println!("Part {} not implemented for day 3", part);
             return;
         },
     };
+    let options = flag_value(args, "--batteries").and_then(|custom| {
+        check_banks(&banks, custom.unwrap_or(default))?;
+        Ok(custom)
+    });
+    if let Ok(batteries) = options
+        && has_flag(args, "--explain")
+    {
+        print_explanation(&banks, batteries.unwrap_or(default));
+    }
+    let result = match (part, options) {
+        (_, Ok(Some(batteries))) => get_max_joltage(&banks, batteries),
+        (1, Ok(None)) => part1(&banks),
+        (_, Ok(None)) => part2(&banks),
+        (_, Err(err)) => {
+            println!("{}", err);
+            return;
+        },
+    };
 
     println!("Day 3 Part {}: {}", part, result);
 }
 
-fn part1(banks: &[&str]) -> u64 {
-    get_max_joltage(banks, 2)
+/// Ensures every bank holds only digits and at least `batteries` of them.
+fn check_banks(banks: &[&str], batteries: usize) -> Result<(), String> {
+    for (idx, bank) in banks.iter().enumerate() {
+        if let Some(c) = bank.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(format!("Bank {}: invalid battery '{}'", idx + 1, c));
+        }
+        if bank.len() < batteries {
+            return Err(format!(
+                "Bank {}: only {} batteries, cannot turn on {}",
+                idx + 1,
+                bank.len(),
+                batteries
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn part1(banks: &[&str]) -> BigUint {
+    get_max_joltage(banks, PART_1_BATTERIES)
+}
+
+fn part2(banks: &[&str]) -> BigUint {
+    get_max_joltage(banks, PART_2_BATTERIES)
 }
 
-fn part2(banks: &[&str]) -> u64 {
-    get_max_joltage(banks, 12)
+/// Batteries turned on in a bank, by position in the bank, and the joltage they produce.
+#[derive(Debug, Clone, PartialEq)]
+struct Selection {
+    digits: String,
+    positions: Vec<usize>,
+    value: BigUint,
 }
 
-fn get_max_joltage(banks: &[&str], n_batteries: usize) -> u64 {
+/// Picks the `k` batteries forming the largest joltage, keeping their order in the bank.
+///
+/// Walks the bank once with a stack of chosen positions: a battery evicts the smaller ones
+/// before it as long as enough batteries remain to fill the selection. The joltage is exact
+/// whatever `k`; `None` means the bank is too short or holds a non-digit.
+fn select_batteries(bank: &[u8], k: usize) -> Option<Selection> {
+    if bank.len() < k || !bank.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let mut drops = bank.len() - k;
+    let mut chosen: Vec<usize> = Vec::with_capacity(k);
+    for (idx, &battery) in bank.iter().enumerate() {
+        while drops > 0 && chosen.last().is_some_and(|&top| bank[top] < battery) {
+            chosen.pop();
+            drops -= 1;
+        }
+        if chosen.len() < k {
+            chosen.push(idx);
+        } else {
+            drops -= 1;
+        }
+    }
+    let digits: Vec<u8> = chosen.iter().map(|&idx| bank[idx] - b'0').collect();
+    Some(Selection {
+        digits: chosen.iter().map(|&idx| bank[idx] as char).collect(),
+        value: BigUint::from_digits(&digits),
+        positions: chosen,
+    })
+}
+
+fn select_all(banks: &[&str], n_batteries: usize) -> Vec<Selection> {
     banks
         .iter()
-        .enumerate()
-        .map(|(bank_idx, bank)| {
-            let mut current_idx = 0;
-            let mut batteries = "".to_owned();
+        .map(|bank| {
+            select_batteries(bank.as_bytes(), n_batteries)
+                .expect("Bank cannot supply the requested batteries")
+        })
+        .collect()
+}
 
-            for _ in 0..n_batteries {
-                let n_take = bank.len() + batteries.len() + 1 - n_batteries - current_idx;
-                let (max_idx, max_char) = bank
-                    .chars()
-                    .skip(current_idx)
-                    .take(n_take)
-                    .enumerate()
-                    .max_by(|(_, a), (_, b)| a.cmp(b).then(Ordering::Greater))
-                    .unwrap();
+fn get_max_joltage(banks: &[&str], n_batteries: usize) -> BigUint {
+    select_all(banks, n_batteries).into_iter().map(|selection| selection.value).sum()
+}
 
-                current_idx += max_idx + 1;
-                batteries.push(max_char);
+/// The bank with its chosen batteries highlighted in bold green.
+fn render_bank(bank: &str, selection: &Selection) -> String {
+    let mut chosen = selection.positions.iter().peekable();
+    bank.chars()
+        .enumerate()
+        .map(|(idx, battery)| {
+            if chosen.next_if_eq(&&idx).is_some() {
+                format!("\x1b[1;32m{}\x1b[0m", battery)
+            } else {
+                battery.to_string()
             }
-
-            println!("[bank {bank_idx}]: {batteries}");
-
-            batteries.parse::<u64>().unwrap()
         })
-        .sum()
+        .collect()
+}
+
+fn print_explanation(banks: &[&str], n_batteries: usize) {
+    for (idx, (bank, selection)) in banks.iter().zip(select_all(banks, n_batteries)).enumerate() {
+        println!("[bank {}] {} -> {}", idx + 1, render_bank(bank, &selection), selection.value);
+    }
 }
 
 #[cfg(test)]
@@ -65,25 +153,87 @@ mod tests {
     fn test_example() {
         let input =
             vec!["987654321111111", "811111111111119", "234234234234278", "818181911112111"];
-        assert_eq!(part1(&input), 357);
+        assert_eq!(part1(&input), BigUint::from(357u64));
     }
 
     #[test]
     fn test_tricky() {
         let input = vec!["91111111191"];
-        assert_eq!(part1(&input), 99);
+        assert_eq!(part1(&input), BigUint::from(99u64));
     }
 
     #[test]
     fn test_simple() {
         let input = vec!["911118"];
-        assert_eq!(get_max_joltage(&input, 3), 918);
+        assert_eq!(get_max_joltage(&input, 3), BigUint::from(918u64));
     }
 
     #[test]
     fn test_part_2() {
         let input =
             vec!["987654321111111", "811111111111119", "234234234234278", "818181911112111"];
-        assert_eq!(part2(&input), 3121910778619);
+        assert_eq!(part2(&input), BigUint::from(3121910778619u64));
+    }
+
+    fn brute_force(bank: &str, k: usize) -> u64 {
+        let n = bank.len();
+        (0u32..1 << n)
+            .filter(|mask| mask.count_ones() as usize == k)
+            .map(|mask| {
+                let digits: String = bank
+                    .chars()
+                    .enumerate()
+                    .filter(|(i, _)| mask >> i & 1 == 1)
+                    .map(|(_, c)| c)
+                    .collect();
+                digits.parse().unwrap_or(0)
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_select_batteries() {
+        let selection = select_batteries(b"911118", 3).unwrap();
+        assert_eq!(
+            selection,
+            Selection { digits: "918".to_string(), positions: vec![0, 1, 5], value: 918u64.into() }
+        );
+        assert_eq!(select_batteries(b"818181911112111", 2).unwrap().positions, vec![6, 11]);
+        assert_eq!(select_batteries(b"12", 3), None);
+        assert_eq!(select_batteries(b"1x2", 2), None);
+
+        for bank in ["3141592653589", "2718281828459", "1111111", "9876501234"] {
+            for k in 1..=bank.len() {
+                let selection = select_batteries(bank.as_bytes(), k).unwrap();
+                assert_eq!(selection.value.to_u128(), Some(brute_force(bank, k) as u128));
+            }
+        }
+    }
+
+    #[test]
+    fn test_many_batteries() {
+        let bank = "98765432101234567890".repeat(3);
+        let banks = vec![bank.as_str(), bank.as_str()];
+        let joltage = select_batteries(bank.as_bytes(), 40).unwrap().value;
+        assert_eq!(joltage.to_string(), "9998765432123456789098765432101234567890");
+        assert_eq!(joltage.to_u128(), None);
+        assert_eq!(get_max_joltage(&banks, 40), joltage.clone() + joltage);
+
+        assert!(check_banks(&banks, 60).is_ok());
+        assert_eq!(
+            check_banks(&banks, 61),
+            Err("Bank 1: only 60 batteries, cannot turn on 61".to_string())
+        );
+        assert_eq!(check_banks(&["12a"], 2), Err("Bank 1: invalid battery 'a'".to_string()));
+    }
+
+    #[test]
+    fn test_render_bank() {
+        let selection = select_batteries(b"818181911112111", 2).unwrap();
+        assert_eq!(
+            render_bank("818181911112111", &selection),
+            "818181\x1b[1;32m9\x1b[0m1111\x1b[1;32m2\x1b[0m111"
+        );
     }
 }
@@ -1,9 +1,116 @@
-use std::fs;
+use std::{collections::VecDeque, fs, str::FromStr};
 
-const NEIGHBORS: [(isize, isize); 8] =
+use crate::utils::cli::{flag_value, has_flag};
+
+const MOORE: [(isize, isize); 8] =
     [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+const VON_NEUMANN: [(isize, isize); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+/// Cells a roll looks at, as `(row, column)` offsets.
+#[derive(Debug, Clone, PartialEq)]
+enum Neighborhood {
+    Moore,
+    VonNeumann,
+    Custom(Vec<(isize, isize)>),
+}
+
+impl Neighborhood {
+    fn offsets(&self) -> &[(isize, isize)] {
+        match self {
+            Neighborhood::Moore => &MOORE,
+            Neighborhood::VonNeumann => &VON_NEUMANN,
+            Neighborhood::Custom(offsets) => offsets,
+        }
+    }
+}
+
+impl FromStr for Neighborhood {
+    type Err = String;
+
+    /// `moore`, `von-neumann`, or custom offsets such as `-1,0;1,0`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "moore" => Ok(Neighborhood::Moore),
+            "von-neumann" => Ok(Neighborhood::VonNeumann),
+            _ => s
+                .split(';')
+                .map(|offset| {
+                    offset
+                        .split_once(',')
+                        .and_then(|(di, dj)| Some((di.trim().parse().ok()?, dj.trim().parse().ok()?)))
+                        .ok_or(format!(
+                            "Invalid offset '{}', expected moore, von-neumann or offsets like -1,0;1,0",
+                            offset
+                        ))
+                })
+                .collect::<Result<_, _>>()
+                .map(Neighborhood::Custom),
+        }
+    }
+}
+
+/// When a roll can be reached by a forklift: fewer than `threshold` of the cells in its
+/// neighbourhood hold a roll. On a toroidal grid, neighbourhoods wrap around the edges.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    threshold: usize,
+    neighborhood: Neighborhood,
+    roll: char,
+    toroidal: bool,
+}
 
-pub fn run(part: u8, is_test: bool) {
+impl Default for Rule {
+    fn default() -> Self {
+        Rule { threshold: 4, neighborhood: Neighborhood::Moore, roll: '@', toroidal: false }
+    }
+}
+
+impl Rule {
+    /// The cell at `offset` from `(i, j)`, if it lies on the grid.
+    fn shift(
+        &self,
+        rows: &[Vec<char>],
+        (i, j): (usize, usize),
+        offset: (isize, isize),
+    ) -> Option<(usize, usize)> {
+        let (mut i, mut j) = (i as isize + offset.0, j as isize + offset.1);
+        if self.toroidal {
+            i = i.rem_euclid(rows.len() as isize);
+            j = j.rem_euclid(rows[i as usize].len().max(1) as isize);
+        }
+        let row = rows.get(usize::try_from(i).ok()?)?;
+        row.get(usize::try_from(j).ok()?).map(|_| (i as usize, j as usize))
+    }
+
+    fn is_roll(&self, rows: &[Vec<char>], (i, j): (usize, usize)) -> bool {
+        rows[i][j] == self.roll
+    }
+
+    /// Rolls in the neighbourhood of `cell`, once per offset reaching them.
+    fn neighbors<'a>(
+        &'a self,
+        rows: &'a [Vec<char>],
+        cell: (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.neighborhood
+            .offsets()
+            .iter()
+            .filter_map(move |&offset| self.shift(rows, cell, offset))
+            .filter(|&cell| self.is_roll(rows, cell))
+    }
+}
+
+fn parse_rule(args: &[String]) -> Result<Rule, String> {
+    let default = Rule::default();
+    Ok(Rule {
+        threshold: flag_value(args, "--threshold")?.unwrap_or(default.threshold),
+        neighborhood: flag_value(args, "--neighborhood")?.unwrap_or(default.neighborhood),
+        roll: flag_value(args, "--roll")?.unwrap_or(default.roll),
+        toroidal: has_flag(args, "--toroidal"),
+    })
+}
+
+pub fn run(part: u8, is_test: bool, args: &[String]) {
     let input_file = if is_test { "test_input.txt" } else { "input.txt" };
     let input = fs::read_to_string(format!("src/day_04/{}", input_file))
         .expect("Failed to read input file");
@@ -11,9 +118,35 @@ pub fn run(part: u8, is_test: bool) {
     // Parse input here
     let rows: Vec<Vec<char>> = input.lines().map(|line| line.chars().collect()).collect();
 
+    let options = parse_rule(args).and_then(|rule| Ok((rule, flag_value(args, "--export")?)));
+    let (rule, export): (Rule, Option<String>) = match options {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        },
+    };
+    if has_flag(args, "--waves") || export.is_some() {
+        let waves = peel(&rows, &rule);
+        if has_flag(args, "--waves") {
+            print_timeline(&summarize(&rows, &rule, &waves));
+        }
+        if let Some(path) = export {
+            let written = if path.ends_with(".ppm") {
+                fs::write(&path, render_ppm(&rows, &rule, &waves))
+            } else {
+                fs::write(&path, render_waves(&rows, &waves))
+            };
+            if let Err(err) = written {
+                println!("Failed to export {}: {}", path, err);
+                return;
+            }
+        }
+    }
+
     let result = match part {
-        1 => part1(&rows),
-        2 => part2(&rows),
+        1 => part1(&rows, &rule),
+        2 => part2(&rows, &rule),
         _ => {
             println!("Part {} not implemented for day 4", part);
             return;
@@ -23,28 +156,15 @@ pub fn run(part: u8, is_test: bool) {
     println!("Day 4 Part {}: {}", part, result);
 }
 
-fn part1(rows: &[Vec<char>]) -> usize {
-    let rolls = find_rolls_to_remove(rows);
+fn part1(rows: &[Vec<char>], rule: &Rule) -> usize {
+    let rolls = find_rolls_to_remove(rows, rule);
     rolls.len()
 }
-fn find_rolls_to_remove(rows: &[Vec<char>]) -> Vec<(usize, usize)> {
-    let height = rows.len() as isize;
-    let width = rows[0].len() as isize;
+fn find_rolls_to_remove(rows: &[Vec<char>], rule: &Rule) -> Vec<(usize, usize)> {
     let mut rolls: Vec<(usize, usize)> = vec![];
     for (i, row) in rows.iter().enumerate() {
-        for (j, _) in row.iter().enumerate().filter(|(_, c)| **c == '@') {
-            let n_adjacent_paper_rolls = NEIGHBORS
-                .iter()
-                .filter(|(dr, dc)| {
-                    let (row, col) = (i as isize + dr, j as isize + dc);
-                    if (0..height).contains(&row) && (0..width).contains(&col) {
-                        return rows[row as usize][col as usize] == '@';
-                    }
-                    false
-                })
-                .count();
-            println!("[{},{}]: {}", i, j, n_adjacent_paper_rolls);
-            if n_adjacent_paper_rolls < 4 {
+        for (j, _) in row.iter().enumerate().filter(|(_, c)| **c == rule.roll) {
+            if rule.neighbors(rows, (i, j)).count() < rule.threshold {
                 rolls.push((i, j));
             }
         }
@@ -52,25 +172,363 @@ fn find_rolls_to_remove(rows: &[Vec<char>]) -> Vec<(usize, usize)> {
     rolls
 }
 
-fn part2(rows: &[Vec<char>]) -> usize {
-    let mut current_rows = rows.to_vec();
-    let mut current_number_of_rolls = 0;
-    loop {
-        let rolls = find_rolls_to_remove(&current_rows);
-        if rolls.is_empty() {
-            break;
+/// Wave in which each roll gets removed when rolls keep being taken away, wave 1 holding the
+/// rolls accessible right away. Rolls that stay, and empty cells, are `None`.
+///
+/// Neighbour counts are computed once, along with the reverse adjacency (the rolls having each
+/// roll in their neighbourhood, which differ from its own neighbours for asymmetric offsets or
+/// ragged toroidal grids). Removing a roll decrements the rolls watching it and queues those
+/// dropping below the threshold for the next wave, so each roll is handled a single time.
+fn peel(rows: &[Vec<char>], rule: &Rule) -> Vec<Vec<Option<usize>>> {
+    let mut counts: Vec<Vec<usize>> = rows.iter().map(|row| vec![0; row.len()]).collect();
+    let mut watchers: Vec<Vec<Vec<(usize, usize)>>> =
+        rows.iter().map(|row| vec![Vec::new(); row.len()]).collect();
+    for (i, row) in rows.iter().enumerate() {
+        for (j, _) in row.iter().enumerate().filter(|(_, c)| **c == rule.roll) {
+            for (ni, nj) in rule.neighbors(rows, (i, j)) {
+                counts[i][j] += 1;
+                watchers[ni][nj].push((i, j));
+            }
+        }
+    }
+    let mut waves: Vec<Vec<Option<usize>>> = rows.iter().map(|row| vec![None; row.len()]).collect();
+    let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
+    for (i, j) in find_rolls_to_remove(rows, rule) {
+        waves[i][j] = Some(1);
+        queue.push_back((i, j));
+    }
+
+    while let Some((i, j)) = queue.pop_front() {
+        let wave = waves[i][j].expect("Queued rolls have a wave");
+        for &(wi, wj) in &watchers[i][j] {
+            counts[wi][wj] -= 1;
+            if counts[wi][wj] < rule.threshold && waves[wi][wj].is_none() {
+                waves[wi][wj] = Some(wave + 1);
+                queue.push_back((wi, wj));
+            }
         }
-        current_number_of_rolls += rolls.len();
-        for (i, j) in rolls {
-            current_rows[i][j] = '*';
+    }
+    waves
+}
+
+fn part2(rows: &[Vec<char>], rule: &Rule) -> usize {
+    peel(rows, rule).iter().flatten().filter(|wave| wave.is_some()).count()
+}
+
+/// Outcome of one wave of removals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WaveSummary {
+    wave: usize,
+    removed: usize,
+    remaining: usize,
+}
+
+fn summarize(rows: &[Vec<char>], rule: &Rule, waves: &[Vec<Option<usize>>]) -> Vec<WaveSummary> {
+    let mut removed: Vec<usize> = Vec::new();
+    for &wave in waves.iter().flatten().flatten() {
+        if removed.len() < wave {
+            removed.resize(wave, 0);
         }
+        removed[wave - 1] += 1;
     }
-    current_number_of_rolls
+    let mut remaining = rows.iter().flatten().filter(|&&c| c == rule.roll).count();
+    removed
+        .into_iter()
+        .enumerate()
+        .map(|(idx, removed)| {
+            remaining -= removed;
+            WaveSummary { wave: idx + 1, removed, remaining }
+        })
+        .collect()
+}
+
+fn print_timeline(timeline: &[WaveSummary]) {
+    for summary in timeline {
+        println!(
+            "[wave {}]: {} removed, {} remaining",
+            summary.wave, summary.removed, summary.remaining
+        );
+    }
+}
+
+/// The grid with each removed roll replaced by its wave in base 36 (`1`-`9`, then `a`-`z`, `+`
+/// past wave 35); rolls that stay and empty cells are left as they are.
+fn render_waves(rows: &[Vec<char>], waves: &[Vec<Option<usize>>]) -> String {
+    rows.iter()
+        .zip(waves)
+        .map(|(row, row_waves)| {
+            let mut line: String = row
+                .iter()
+                .zip(row_waves)
+                .map(|(&c, wave)| match wave {
+                    Some(wave) => char::from_digit(*wave as u32, 36).unwrap_or('+'),
+                    None => c,
+                })
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Side of the square drawn for each cell in exported images.
+const PIXELS_PER_CELL: usize = 4;
+
+/// Binary PPM image of the grid: empty cells are black, rolls that stay are white and removed
+/// rolls go from red in the first wave to blue in the last one.
+fn render_ppm(rows: &[Vec<char>], rule: &Rule, waves: &[Vec<Option<usize>>]) -> Vec<u8> {
+    let last_wave = waves.iter().flatten().flatten().max().copied().unwrap_or(1).max(2);
+    let color = |i: usize, j: usize| -> [u8; 3] {
+        match (rows[i].get(j), waves[i].get(j).copied().flatten()) {
+            (_, Some(wave)) => {
+                let t = (wave - 1) * 255 / (last_wave - 1);
+                [255 - t as u8, 0, t as u8]
+            },
+            (Some(&c), None) if c == rule.roll => [255, 255, 255],
+            _ => [0, 0, 0],
+        }
+    };
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut image =
+        format!("P6\n{} {}\n255\n", width * PIXELS_PER_CELL, rows.len() * PIXELS_PER_CELL)
+            .into_bytes();
+    for i in 0..rows.len() {
+        let line: Vec<u8> = (0..width).flat_map(|j| color(i, j).repeat(PIXELS_PER_CELL)).collect();
+        for _ in 0..PIXELS_PER_CELL {
+            image.extend_from_slice(&line);
+        }
+    }
+    image
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::utils::rng::Lcg;
+
+    fn grid(lines: &[&str]) -> Vec<Vec<char>> {
+        lines.iter().map(|line| line.chars().collect()).collect()
+    }
+
+    fn example() -> Vec<Vec<char>> {
+        grid(&[
+            "..@@.@@@@.",
+            "@@@.@.@.@@",
+            "@@@@@.@.@@",
+            "@.@@@@..@.",
+            "@@.@@@@.@@",
+            ".@@@@@@@.@",
+            ".@.@.@.@@@",
+            "@.@@@.@@@@",
+            ".@@@@@@@@.",
+            "@.@.@@@.@.",
+        ])
+    }
+
+    /// Reference implementation: rescans the whole grid after each wave of removals.
+    fn peel_iteratively(rows: &[Vec<char>], rule: &Rule) -> Vec<Vec<Option<usize>>> {
+        let mut current_rows = rows.to_vec();
+        let mut waves: Vec<Vec<Option<usize>>> =
+            rows.iter().map(|row| vec![None; row.len()]).collect();
+        for wave in 1.. {
+            let rolls = find_rolls_to_remove(&current_rows, rule);
+            if rolls.is_empty() {
+                break;
+            }
+            for (i, j) in rolls {
+                current_rows[i][j] = '\0';
+                waves[i][j] = Some(wave);
+            }
+        }
+        waves
+    }
+
+    fn pattern_grid() -> Vec<Vec<char>> {
+        (0..24)
+            .map(|i: usize| {
+                (0..31)
+                    .map(|j: usize| if (i * 7 + j * 13 + i * j) % 7 < 6 { '@' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_peel_matches_iterative() {
+        let rows = pattern_grid();
+        let waves = peel(&rows, &Rule::default());
+        assert_eq!(waves, peel_iteratively(&rows, &Rule::default()));
+        assert!(waves.iter().flatten().any(|wave| *wave > Some(2)));
+    }
+
+    #[test]
+    fn test_rule_variants() {
+        let rows = pattern_grid();
+        let rules = [
+            Rule { threshold: 2, neighborhood: Neighborhood::VonNeumann, ..Rule::default() },
+            Rule { threshold: 5, toroidal: true, ..Rule::default() },
+            Rule {
+                threshold: 3,
+                neighborhood: Neighborhood::VonNeumann,
+                toroidal: true,
+                ..Rule::default()
+            },
+            // Knight moves plus one step right: not symmetric
+            Rule {
+                threshold: 3,
+                neighborhood: "-2,1;1,2;2,-1;-1,-2;0,1".parse().unwrap(),
+                ..Rule::default()
+            },
+            Rule {
+                threshold: 4,
+                neighborhood: "0,1;0,2".parse().unwrap(),
+                toroidal: true,
+                ..Rule::default()
+            },
+        ];
+        for rule in rules {
+            assert_eq!(peel(&rows, &rule), peel_iteratively(&rows, &rule), "{:?}", rule);
+        }
+    }
 
     #[test]
-    fn test_example() {}
+    fn test_parse_rule() {
+        let args: Vec<String> =
+            ["--threshold", "3", "--neighborhood", "von-neumann", "--roll", "#", "--toroidal"]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect();
+        assert_eq!(
+            parse_rule(&args),
+            Ok(Rule {
+                threshold: 3,
+                neighborhood: Neighborhood::VonNeumann,
+                roll: '#',
+                toroidal: true
+            })
+        );
+        assert_eq!(parse_rule(&[]), Ok(Rule::default()));
+        assert_eq!("1,0; -1,2".parse(), Ok(Neighborhood::Custom(vec![(1, 0), (-1, 2)])));
+        assert!("1;0".parse::<Neighborhood>().is_err());
+
+        // The same grid drawn with another symbol
+        let rows: Vec<Vec<char>> = pattern_grid()
+            .iter()
+            .map(|row| row.iter().map(|&c| if c == '@' { '#' } else { c }).collect())
+            .collect();
+        let rule = Rule { roll: '#', ..Rule::default() };
+        assert_eq!(part2(&rows, &rule), part2(&pattern_grid(), &Rule::default()));
+    }
+
+    #[test]
+    fn test_timeline_and_export() {
+        let rows: Vec<Vec<char>> =
+            ["@@@@@", "@@@@@", "@@@@@", "..@.."].iter().map(|row| row.chars().collect()).collect();
+        let rule = Rule::default();
+        let waves = peel(&rows, &rule);
+        assert_eq!(
+            summarize(&rows, &rule, &waves),
+            vec![
+                WaveSummary { wave: 1, removed: 5, remaining: 11 },
+                WaveSummary { wave: 2, removed: 2, remaining: 9 },
+                WaveSummary { wave: 3, removed: 4, remaining: 5 },
+                WaveSummary { wave: 4, removed: 4, remaining: 1 },
+                WaveSummary { wave: 5, removed: 1, remaining: 0 },
+            ]
+        );
+        assert_eq!(render_waves(&rows, &waves), "13431\n24542\n13431\n..1..\n");
+
+        let image = render_ppm(&rows, &rule, &waves);
+        let header = format!("P6\n{} {}\n255\n", 5 * PIXELS_PER_CELL, 4 * PIXELS_PER_CELL);
+        assert!(image.starts_with(header.as_bytes()));
+        assert_eq!(image.len(), header.len() + 3 * 20 * PIXELS_PER_CELL * PIXELS_PER_CELL);
+        // Top-left cell goes in the first wave, the one below it in the second of five
+        assert_eq!(image[header.len()..header.len() + 3], [255, 0, 0]);
+        let below = header.len() + 3 * 5 * PIXELS_PER_CELL * PIXELS_PER_CELL;
+        assert_eq!(image[below..below + 3], [192, 0, 63]);
+    }
+
+    #[test]
+    fn test_example() {
+        let rows = example();
+        assert_eq!(part1(&rows, &Rule::default()), 13);
+        let accessible = find_rolls_to_remove(&rows, &Rule::default());
+        assert_eq!(&accessible[..4], [(0, 2), (0, 3), (0, 5), (0, 6)]);
+        assert!(accessible.contains(&(9, 8)) && !accessible.contains(&(0, 7)));
+    }
+
+    #[test]
+    fn test_example_part_2() {
+        let rows = example();
+        assert_eq!(part2(&rows, &Rule::default()), 43);
+        assert_eq!(peel(&rows, &Rule::default()), peel_iteratively(&rows, &Rule::default()));
+    }
+
+    #[test]
+    fn test_empty_grid() {
+        for rows in [vec![], grid(&[""]), grid(&["", ""])] {
+            assert_eq!(part1(&rows, &Rule::default()), 0);
+            assert_eq!(part2(&rows, &Rule::default()), 0);
+            let toroidal = Rule { toroidal: true, ..Rule::default() };
+            assert_eq!(part2(&rows, &toroidal), 0);
+        }
+        assert_eq!(part2(&grid(&["....", ".."]), &Rule::default()), 0);
+    }
+
+    #[test]
+    fn test_single_row() {
+        // At most two neighbours each, so every roll is accessible straight away
+        let rows = grid(&["@@@.@@"]);
+        assert_eq!(part1(&rows, &Rule::default()), 5);
+        assert_eq!(part2(&rows, &Rule::default()), 5);
+
+        let strict = Rule { threshold: 2, ..Rule::default() };
+        assert_eq!(part1(&rows, &strict), 4);
+        assert_eq!(part2(&rows, &strict), 5);
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let mut grids = vec![
+            grid(&["@@@@@@", "@@", "@@@@", "", "@@@@@@@", "@@@"]),
+            // Wrapping each row by its own length is not symmetric on these
+            grid(&["@.", "@", "@@", "@..", ".@"]),
+            grid(&["@@@@@@.", "..", "@@@@", ".@@@@", "@@.@.@."]),
+        ];
+        // Deterministic pseudo-random ragged grids
+        let mut rng = Lcg::new(2025);
+        for _ in 0..200 {
+            let rows = (0..1 + rng.below(6))
+                .map(|_| {
+                    (0..rng.below(8)).map(|_| if rng.below(3) == 0 { '.' } else { '@' }).collect()
+                })
+                .collect();
+            grids.push(rows);
+        }
+        for rows in &grids {
+            for rule in [
+                Rule::default(),
+                Rule { toroidal: true, ..Rule::default() },
+                Rule {
+                    threshold: 2,
+                    neighborhood: Neighborhood::VonNeumann,
+                    toroidal: true,
+                    ..Rule::default()
+                },
+            ] {
+                assert_eq!(
+                    peel(rows, &rule),
+                    peel_iteratively(rows, &rule),
+                    "{:?} {:?}",
+                    rows,
+                    rule
+                );
+            }
+        }
+        // Cells past the end of a shorter row count as empty
+        let rows = grid(&["@@@@", "@", "@@@@"]);
+        assert_eq!(find_rolls_to_remove(&rows, &Rule::default()).len(), 8);
+        assert_eq!(render_waves(&rows, &peel(&rows, &Rule::default())), "1111\n2\n1111\n");
+    }
 }
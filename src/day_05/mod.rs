@@ -1,14 +1,48 @@
-use std::fs;
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+};
 
-pub fn run(part: u8, is_test: bool) {
+use crate::utils::cli::has_flag;
+
+/// Inclusive range of fresh ingredient IDs.
+type Range = (u64, u64);
+
+pub fn run(part: u8, is_test: bool, args: &[String]) {
     let input_file = if is_test { "test_input.txt" } else { "input.txt" };
-    let input = fs::read_to_string(format!("src/day_05/{}", input_file))
-        .expect("Failed to read input file");
+    let path = format!("src/day_05/{}", input_file);
+    if has_flag(args, "--stream") {
+        let reader = BufReader::new(File::open(&path).expect("Failed to read input file"));
+        let result = match part {
+            1 => count_fresh(reader).map(|count| count as u128),
+            2 => read_ranges(&mut numbered_lines(reader)).map(|ranges| part2(&ranges)),
+            _ => {
+                println!("Part {} not implemented for day 5", part);
+                return;
+            },
+        };
+        match result {
+            Ok(result) => println!("Day 5 Part {}: {}", part, result),
+            Err(err) => println!("{}", err),
+        }
+        return;
+    }
 
-    let (ranges, items) = parse_input(&input);
+    let input = fs::read_to_string(&path).expect("Failed to read input file");
+    let (ranges, items) = match parse_input(&input) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        },
+    };
+    if has_flag(args, "--matches") {
+        print_matches(&ranges, &items);
+    }
 
     let result = match part {
-        1 => part1(&ranges, items),
+        1 => part1(&ranges, items) as u128,
         2 => part2(&ranges),
         _ => {
             println!("Part {} not implemented for day 5", part);
@@ -19,49 +53,164 @@ pub fn run(part: u8, is_test: bool) {
     println!("Day 5 Part {}: {}", part, result);
 }
 
-fn parse_input(input: &str) -> (Vec<(i64, i64)>, Vec<i64>) {
-    let (ranges_str, items_str) = input.split_once("\n\n").unwrap();
+/// Lines of the database, numbered from 1, with read failures turned into errors.
+fn numbered_lines(reader: impl BufRead) -> impl Iterator<Item = Result<(usize, String), String>> {
+    reader.lines().enumerate().map(|(idx, line)| {
+        line.map(|line| (idx + 1, line)).map_err(|err| format!("Failed to read input: {}", err))
+    })
+}
+
+fn parse_range(line: &str) -> Result<Range, String> {
+    let (start, end) = line.split_once('-').ok_or("missing '-'")?;
+    let bound = |bound: &str| {
+        bound.trim().parse::<u64>().map_err(|err| format!("invalid ID '{}': {}", bound.trim(), err))
+    };
+    let (start, end) = (bound(start)?, bound(end)?);
+    if start > end {
+        return Err(format!("start {} is after end {}", start, end));
+    }
+    Ok((start, end))
+}
+
+/// Parses a non-blank ingredient line, or `None` for a blank one.
+fn parse_item(number: usize, line: &str) -> Result<Option<u64>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    line.parse()
+        .map(Some)
+        .map_err(|err| format!("Line {} ({}): invalid ingredient ID: {}", number, line, err))
+}
+
+/// Reads the fresh ranges, up to and including the blank line that separates them from the
+/// ingredients.
+fn read_ranges(
+    lines: &mut impl Iterator<Item = Result<(usize, String), String>>,
+) -> Result<Vec<Range>, String> {
+    let mut ranges = Vec::new();
+    for line in lines {
+        let (number, line) = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(ranges);
+        }
+        ranges
+            .push(parse_range(line).map_err(|err| format!("Line {} ({}): {}", number, line, err))?);
+    }
+    Err("Missing blank line between the fresh ranges and the ingredients".to_string())
+}
 
-    let ranges = ranges_str
-        .lines()
-        .filter_map(|line| {
-            let (start, end) = line.split_once('-')?;
-            Some((start.parse().ok()?, end.parse().ok()?))
-        })
-        .collect();
+/// Parses the whole database; Windows line endings are accepted.
+fn parse_input(input: &str) -> Result<(Vec<Range>, Vec<u64>), String> {
+    let mut lines = numbered_lines(input.as_bytes());
+    let ranges = read_ranges(&mut lines)?;
+    let mut items = Vec::new();
+    for line in lines {
+        let (number, line) = line?;
+        items.extend(parse_item(number, &line)?);
+    }
+    Ok((ranges, items))
+}
 
-    let items = items_str.lines().filter_map(|line| line.parse().ok()).collect();
+/// Counts the fresh ingredients without holding them in memory: only the ranges are kept, and
+/// ingredient IDs are checked one line at a time as they are read.
+fn count_fresh(reader: impl BufRead) -> Result<usize, String> {
+    let mut lines = numbered_lines(reader);
+    let index = RangeIndex::new(&read_ranges(&mut lines)?);
+    let mut count = 0;
+    for line in lines {
+        let (number, line) = line?;
+        if parse_item(number, &line)?.is_some_and(|item| index.contains(item)) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
 
-    (ranges, items)
+/// Fresh ranges merged into disjoint, sorted ranges so that lookups are binary searches.
+struct RangeIndex {
+    merged: Vec<Range>,
 }
 
-fn part1(fresh_ranges: &[(i64, i64)], items: Vec<i64>) -> i64 {
-    items
-        .into_iter()
-        .filter(|item| {
-            for (start, end) in fresh_ranges {
-                if start <= item && item <= end {
-                    return true;
-                }
+impl RangeIndex {
+    fn new(fresh_ranges: &[Range]) -> Self {
+        let mut ranges = fresh_ranges.to_vec();
+        ranges.sort_unstable();
+
+        let merged = ranges.into_iter().fold(Vec::<Range>::new(), |mut acc, (start, end)| {
+            match acc.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => acc.push((start, end)),
             }
-            false
-        })
-        .count() as i64
+            acc
+        });
+        RangeIndex { merged }
+    }
+
+    fn contains(&self, item: u64) -> bool {
+        // Only the last range starting at or before `item` can hold it
+        let idx = self.merged.partition_point(|&(start, _)| start <= item);
+        idx > 0 && item <= self.merged[idx - 1].1
+    }
+
+    /// Number of IDs in the ranges, which reaches 2⁶⁴ when they span the whole domain.
+    fn covered(&self) -> u128 {
+        self.merged.iter().map(|(s, e)| (e - s) as u128 + 1).sum()
+    }
 }
 
-fn part2(fresh_ranges: &[(i64, i64)]) -> i64 {
-    let mut ranges = fresh_ranges.to_vec();
-    ranges.sort_unstable();
+/// For each item, the indices (sorted) of the fresh ranges containing it.
+///
+/// Sweeps the items in increasing order, opening ranges as their start is passed and closing
+/// them once their end is behind, so that each lookup only visits ranges that match.
+fn containing_ranges(fresh_ranges: &[Range], items: &[u64]) -> Vec<Vec<usize>> {
+    let mut by_start: Vec<usize> = (0..fresh_ranges.len()).collect();
+    by_start.sort_unstable_by_key(|&idx| fresh_ranges[idx].0);
+    let mut by_item: Vec<usize> = (0..items.len()).collect();
+    by_item.sort_unstable_by_key(|&idx| items[idx]);
+
+    let mut open: BTreeSet<(u64, usize)> = BTreeSet::new();
+    let mut next = 0;
+    let mut result = vec![Vec::new(); items.len()];
+    for idx in by_item {
+        let item = items[idx];
+        while let Some(&range) = by_start.get(next)
+            && fresh_ranges[range].0 <= item
+        {
+            open.insert((fresh_ranges[range].1, range));
+            next += 1;
+        }
+        while open.first().is_some_and(|&(end, _)| end < item) {
+            open.pop_first();
+        }
+        result[idx] = open.iter().map(|&(_, range)| range).collect();
+        result[idx].sort_unstable();
+    }
+    result
+}
 
-    let merged = ranges.into_iter().fold(Vec::<(i64, i64)>::new(), |mut acc, (start, end)| {
-        match acc.last_mut() {
-            Some(last) if start <= last.1 => last.1 = last.1.max(end),
-            _ => acc.push((start, end)),
+fn print_matches(fresh_ranges: &[Range], items: &[u64]) {
+    for (item, matches) in items.iter().zip(containing_ranges(fresh_ranges, items)) {
+        let matches: Vec<String> = matches
+            .iter()
+            .map(|&idx| format!("{}-{}", fresh_ranges[idx].0, fresh_ranges[idx].1))
+            .collect();
+        if matches.is_empty() {
+            println!("[{}]: spoiled", item);
+        } else {
+            println!("[{}]: fresh in {}", item, matches.join(", "));
         }
-        acc
-    });
+    }
+}
+
+fn part1(fresh_ranges: &[Range], items: Vec<u64>) -> usize {
+    let index = RangeIndex::new(fresh_ranges);
+    items.into_iter().filter(|&item| index.contains(item)).count()
+}
 
-    merged.iter().map(|(s, e)| e - s + 1).sum()
+fn part2(fresh_ranges: &[Range]) -> u128 {
+    RangeIndex::new(fresh_ranges).covered()
 }
 
 #[cfg(test)]
@@ -80,4 +229,79 @@ mod tests {
         let fresh_ranges = [(3, 5), (10, 14), (16, 20), (12, 18)];
         assert_eq!(part2(&fresh_ranges), 14);
     }
+
+    #[test]
+    fn test_range_index() {
+        let index = RangeIndex::new(&[(10, 14), (3, 5), (16, 20), (12, 18), (30, 30)]);
+        assert_eq!(index.merged, vec![(3, 5), (10, 20), (30, 30)]);
+        let fresh: Vec<u64> = (0..35).filter(|&item| index.contains(item)).collect();
+        let expected: Vec<u64> = [3, 4, 5].into_iter().chain(10..=20).chain([30]).collect();
+        assert_eq!(fresh, expected);
+        assert!(!RangeIndex::new(&[]).contains(0));
+    }
+
+    #[test]
+    fn test_containing_ranges() {
+        let fresh_ranges = [(3, 5), (10, 14), (16, 20), (12, 18)];
+        let items = [17, 1, 5, 8, 11, 32, 12, 16];
+        assert_eq!(
+            containing_ranges(&fresh_ranges, &items),
+            vec![vec![2, 3], vec![], vec![0], vec![], vec![1], vec![], vec![1, 3], vec![2, 3]]
+        );
+    }
+
+    const EXAMPLE: &str = "3-5\n10-14\n16-20\n12-18\n\n1\n5\n8\n11\n17\n32\n";
+
+    #[test]
+    fn test_parse_input() {
+        let (ranges, items) = parse_input(EXAMPLE).unwrap();
+        assert_eq!(ranges, vec![(3, 5), (10, 14), (16, 20), (12, 18)]);
+        assert_eq!(items, vec![1, 5, 8, 11, 17, 32]);
+        assert_eq!(parse_input(&EXAMPLE.replace('\n', "\r\n")), Ok((ranges, items)));
+    }
+
+    #[test]
+    fn test_count_fresh() {
+        assert_eq!(count_fresh(EXAMPLE.as_bytes()), Ok(3));
+        assert_eq!(count_fresh(EXAMPLE.replace('\n', "\r\n").as_bytes()), Ok(3));
+        assert_eq!(count_fresh("3-5\n\n".as_bytes()), Ok(0));
+    }
+
+    #[test]
+    fn test_missing_separator() {
+        let error =
+            Err("Missing blank line between the fresh ranges and the ingredients".to_string());
+        assert_eq!(count_fresh("3-5\n10-14\n".as_bytes()), error);
+        assert_eq!(parse_input("3-5\n10-14"), error.map(|_: usize| (vec![], vec![])));
+    }
+
+    #[test]
+    fn test_full_domain() {
+        let ranges = [(0, u64::MAX), (5, 10)];
+        assert_eq!(part2(&ranges), 1 << 64);
+        assert_eq!(part1(&ranges, vec![0, u64::MAX]), 2);
+
+        let input = "18446744073709551600-18446744073709551615\n\n18446744073709551610\n12\n";
+        let (ranges, items) = parse_input(input).unwrap();
+        assert_eq!(part1(&ranges, items), 1);
+        assert_eq!(part2(&ranges), 16);
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        assert_eq!(
+            parse_input("3-5\n10-x\n\n1\n"),
+            Err("Line 2 (10-x): invalid ID 'x': invalid digit found in string".to_string())
+        );
+        assert_eq!(
+            parse_input("3-5\n14-10\n\n1\n"),
+            Err("Line 2 (14-10): start 14 is after end 10".to_string())
+        );
+        assert_eq!(
+            count_fresh("3-5\n\n1\n-4\n".as_bytes()),
+            Err("Line 4 (-4): invalid ingredient ID: invalid digit found in string".to_string())
+        );
+        assert!(parse_input("3-5\n\n18446744073709551616\n").is_err());
+        assert_eq!(parse_input("7\n\n1\n"), Err("Line 1 (7): missing '-'".to_string()));
+    }
 }
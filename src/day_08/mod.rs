@@ -1,13 +1,25 @@
-use std::fs;
+use std::{collections::BTreeMap, fs};
+
+use crate::utils::{
+    cli::{flag_value, has_flag},
+    kdtree::{ClosestPairs, KdTree, Metric},
+};
 
 struct UnionFind {
     parent: Vec<usize>,
     rank: Vec<usize>,
+    size: Vec<usize>,
+    components: usize,
 }
 
 impl UnionFind {
     fn new(size: usize) -> Self {
-        Self { parent: (0..size).collect(), rank: vec![0; size] }
+        Self {
+            parent: (0..size).collect(),
+            rank: vec![0; size],
+            size: vec![1; size],
+            components: size,
+        }
     }
 
     fn find(&mut self, i: usize) -> usize {
@@ -17,103 +29,281 @@ impl UnionFind {
         self.parent[i]
     }
 
-    fn union(&mut self, a: usize, b: usize) {
+    /// Merges the components of `a` and `b`, returning whether they were separate.
+    fn union(&mut self, a: usize, b: usize) -> bool {
         let (ra, rb) = (self.find(a), self.find(b));
-        if ra != rb {
-            match self.rank[ra].cmp(&self.rank[rb]) {
-                std::cmp::Ordering::Less => self.parent[ra] = rb,
-                std::cmp::Ordering::Greater => self.parent[rb] = ra,
-                std::cmp::Ordering::Equal => {
-                    self.parent[rb] = ra;
-                    self.rank[ra] += 1;
-                },
-            }
+        if ra == rb {
+            return false;
         }
+        let (root, child) = match self.rank[ra].cmp(&self.rank[rb]) {
+            std::cmp::Ordering::Less => (rb, ra),
+            std::cmp::Ordering::Greater => (ra, rb),
+            std::cmp::Ordering::Equal => {
+                self.rank[ra] += 1;
+                (ra, rb)
+            },
+        };
+        self.parent[child] = root;
+        self.size[root] += self.size[child];
+        self.components -= 1;
+        true
+    }
+
+    fn component_size(&mut self, i: usize) -> usize {
+        let root = self.find(i);
+        self.size[root]
+    }
+}
+
+/// A group of junction boxes connected together.
+#[derive(Debug, Clone, PartialEq)]
+struct Circuit<const D: usize> {
+    members: Vec<usize>,
+    min: [i64; D],
+    max: [i64; D],
+}
+
+impl<const D: usize> Circuit<D> {
+    fn size(&self) -> usize {
+        self.members.len()
+    }
+}
+
+/// Partition of the junction boxes after connecting the `n_connections` closest pairs, largest
+/// circuits first.
+fn circuits_after<const D: usize>(
+    points: &[[i64; D]],
+    n_connections: usize,
+    metric: Metric,
+) -> Vec<Circuit<D>> {
+    let mut uf = UnionFind::new(points.len());
+    for (_, a, b) in ClosestPairs::new(points, metric).take(n_connections) {
+        uf.union(a, b);
     }
 
-    fn component_sizes(&mut self) -> Vec<usize> {
-        let mut sizes = vec![0; self.parent.len()];
-        for i in 0..self.parent.len() {
-            sizes[self.find(i)] += 1;
+    let mut by_root: BTreeMap<usize, Circuit<D>> = BTreeMap::new();
+    for (idx, point) in points.iter().enumerate() {
+        let circuit = by_root.entry(uf.find(idx)).or_insert(Circuit {
+            members: vec![],
+            min: *point,
+            max: *point,
+        });
+        circuit.members.push(idx);
+        for (axis, &value) in point.iter().enumerate() {
+            circuit.min[axis] = circuit.min[axis].min(value);
+            circuit.max[axis] = circuit.max[axis].max(value);
         }
-        sizes.into_iter().filter(|&s| s > 0).collect()
     }
+
+    let mut circuits: Vec<Circuit<D>> = by_root.into_values().collect();
+    circuits.sort_by(|a, b| b.size().cmp(&a.size()).then(a.members[0].cmp(&b.members[0])));
+    circuits
 }
 
-pub fn run(part: u8, is_test: bool) {
-    let input_file = if is_test { "test_input.txt" } else { "input.txt" };
-    let input = fs::read_to_string(format!("src/day_08/{}", input_file))
-        .expect("Failed to read input file");
+fn print_histogram<const D: usize>(circuits: &[Circuit<D>]) {
+    let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+    for circuit in circuits {
+        *histogram.entry(circuit.size()).or_default() += 1;
+    }
+    println!("{} circuits", circuits.len());
+    for (size, count) in histogram.iter().rev() {
+        println!("  size {:>4}: {}", size, count);
+    }
+}
 
-    let points: Vec<[i64; 3]> = input
-        .lines()
-        .filter_map(|line| {
-            let c: Vec<i64> = line.split(',').filter_map(|n| n.parse().ok()).collect();
-            Some([*c.first()?, *c.get(1)?, *c.get(2)?])
-        })
-        .collect();
+/// Prints how many circuits remain, and the size of the largest, after each connection.
+fn stream_circuits<const D: usize>(points: &[[i64; D]], metric: Metric) {
+    let mut uf = UnionFind::new(points.len());
+    let mut largest = points.len().min(1);
+    for (k, (_, a, b)) in ClosestPairs::new(points, metric).enumerate() {
+        if uf.components <= 1 {
+            break;
+        }
+        if uf.union(a, b) {
+            largest = largest.max(uf.component_size(a));
+        }
+        println!("[connection {}]: {} circuits, largest {}", k + 1, uf.components, largest);
+    }
+}
 
-    let result = match part {
-        1 => part1(&points, 1000),
-        2 => part2(&points),
-        _ => {
-            println!("Part {} not implemented for day 8", part);
-            return;
-        },
-    };
+/// Ordered by weight, then by endpoints, the order in which Kruskal's algorithm would try it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Edge {
+    weight: i128,
+    a: usize,
+    b: usize,
+}
 
-    println!("Day 8 Part {}: {}", part, result);
+struct SpanningTree {
+    /// Accepted edges, in the order Kruskal's algorithm would connect them.
+    edges: Vec<Edge>,
+    /// Sum of the edge weights, or `None` if it overflows an `i128`.
+    total_weight: Option<i128>,
 }
 
-fn part1(points: &[[i64; 3]], n_iterations: usize) -> i64 {
-    let mut pairs: Vec<(i64, usize, usize)> = (0..points.len())
-        .flat_map(|i| {
-            (i + 1..points.len()).map(move |j| {
-                let d: i64 = (0..3).map(|k| (points[i][k] - points[j][k]).pow(2)).sum();
-                (d, i, j)
-            })
-        })
-        .collect();
+impl SpanningTree {
+    /// The edge that finally joins every junction box into a single circuit.
+    fn connecting_edge(&self) -> Option<&Edge> {
+        self.edges.last()
+    }
+}
 
-    pairs.sort_by_key(|p| p.0);
+fn print_spanning_tree<const D: usize>(points: &[[i64; D]], tree: &SpanningTree) {
+    println!("{} edges", tree.edges.len());
+    for edge in &tree.edges {
+        println!("  {:?} - {:?}: {}", points[edge.a], points[edge.b], edge.weight);
+    }
+    match tree.total_weight {
+        Some(total) => println!("total weight: {}", total),
+        None => println!("total weight: overflows an i128"),
+    }
+}
 
+/// Builds the tree with Borůvka's algorithm: each round links every circuit to its closest
+/// box outside it, so at most log₂(n) rounds of one nearest-neighbour query per box are needed,
+/// however clustered the boxes are. Since no two edges compare equal, the tree is unique and
+/// matches the one Kruskal's algorithm would find.
+fn minimum_spanning_tree<const D: usize>(points: &[[i64; D]], metric: Metric) -> SpanningTree {
+    let tree = KdTree::new(points, metric);
     let mut uf = UnionFind::new(points.len());
-    for &(_, a, b) in pairs.iter().take(n_iterations) {
-        uf.union(a, b);
+    let mut edges = Vec::with_capacity(points.len().saturating_sub(1));
+
+    while uf.components > 1 {
+        let circuits: Vec<usize> = (0..points.len()).map(|i| uf.find(i)).collect();
+        let labelled = tree.with_labels(&circuits);
+
+        // Cheapest edge leaving each circuit, indexed by its root
+        let mut cheapest: Vec<Option<Edge>> = vec![None; points.len()];
+        for (i, &circuit) in circuits.iter().enumerate() {
+            let bound = cheapest[circuit].map_or(i128::MAX, |edge| edge.weight);
+            if let Some((weight, j)) = labelled.nearest_outside(i, bound) {
+                let edge = Edge { weight, a: i.min(j), b: i.max(j) };
+                if cheapest[circuit].is_none_or(|best| edge < best) {
+                    cheapest[circuit] = Some(edge);
+                }
+            }
+        }
+
+        for edge in cheapest.into_iter().flatten() {
+            if uf.union(edge.a, edge.b) {
+                edges.push(edge);
+            }
+        }
     }
-    let mut sizes = uf.component_sizes();
-    sizes.sort_by(|a, b| b.cmp(a));
-    let answer: usize = sizes.iter().take(3).product();
-    answer as i64
+    edges.sort_unstable();
+
+    let total_weight = edges.iter().try_fold(0i128, |acc, edge| acc.checked_add(edge.weight));
+    SpanningTree { edges, total_weight }
 }
 
-fn part2(points: &[[i64; 3]]) -> i64 {
-    let mut pairs: Vec<(i64, usize, usize, i64)> = (0..points.len())
-        .flat_map(|i| {
-            (i + 1..points.len()).map(move |j| {
-                let d: i64 = (0..3).map(|k| (points[i][k] - points[j][k]).pow(2)).sum();
-                let x_mul = points[i][0] * points[j][0];
-                (d, i, j, x_mul)
+/// Parses one point of `D` comma-separated coordinates per line, skipping blank lines.
+fn parse_points<const D: usize>(input: &str) -> Result<Vec<[i64; D]>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            let coordinates = line
+                .split(',')
+                .map(|n| {
+                    n.trim().parse::<i64>().map_err(|err| {
+                        format!("Line {}: invalid coordinate '{}': {}", idx + 1, n.trim(), err)
+                    })
+                })
+                .collect::<Result<Vec<i64>, String>>()?;
+            coordinates.try_into().map_err(|c: Vec<i64>| {
+                format!(
+                    "Line {}: expected {} coordinates, found {} ({})",
+                    idx + 1,
+                    D,
+                    c.len(),
+                    line
+                )
             })
         })
-        .collect();
-
-    pairs.sort_by_key(|p| p.0);
+        .collect()
+}
 
-    let mut uf = UnionFind::new(points.len());
-    for &(_, a, b, x_mul) in pairs.iter() {
-        uf.union(a, b);
-        if uf.component_sizes().len() == 1 {
-            return x_mul;
+/// Ensures that every distance between the points fits in an `i128` under `metric`.
+fn check_range<const D: usize>(points: &[[i64; D]], metric: Metric) -> Result<(), String> {
+    let Some(&first) = points.first() else {
+        return Ok(());
+    };
+    let (mut min, mut max) = (first, first);
+    for point in points {
+        for (axis, &value) in point.iter().enumerate() {
+            min[axis] = min[axis].min(value);
+            max[axis] = max[axis].max(value);
         }
     }
 
-    panic!("This should never happen");
+    // No two points are further apart than the corners of their bounding box
+    metric
+        .checked_distance(&min, &max)
+        .map(|_| ())
+        .ok_or(format!("Coordinates span too wide a range for the {:?} metric", metric))
+}
+
+pub fn run(part: u8, is_test: bool, args: &[String]) {
+    let input_file = if is_test { "test_input.txt" } else { "input.txt" };
+    let input = fs::read_to_string(format!("src/day_08/{}", input_file))
+        .expect("Failed to read input file");
+
+    let options = parse_points::<3>(&input).and_then(|points| {
+        let n_connections = flag_value(args, "--connections")?.unwrap_or(1000);
+        let metric = flag_value(args, "--metric")?.unwrap_or(Metric::SquaredEuclidean);
+        check_range(&points, metric)?;
+        Ok((points, n_connections, metric))
+    });
+    let (points, n_connections, metric) = match options {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}", err);
+            return;
+        },
+    };
+
+    if has_flag(args, "--histogram") {
+        print_histogram(&circuits_after(&points, n_connections, metric));
+    }
+    if has_flag(args, "--stream") {
+        stream_circuits(&points, metric);
+    }
+    if has_flag(args, "--tree") {
+        print_spanning_tree(&points, &minimum_spanning_tree(&points, metric));
+    }
+
+    let result = match part {
+        1 => Ok(part1(&points, n_connections, metric)),
+        2 => part2(&points, metric),
+        _ => {
+            println!("Part {} not implemented for day 8", part);
+            return;
+        },
+    };
+
+    match result {
+        Ok(result) => println!("Day 8 Part {}: {}", part, result),
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn part1<const D: usize>(points: &[[i64; D]], n_iterations: usize, metric: Metric) -> i128 {
+    let circuits = circuits_after(points, n_iterations, metric);
+    circuits.iter().take(3).map(|circuit| circuit.size() as i128).product()
+}
+
+fn part2<const D: usize>(points: &[[i64; D]], metric: Metric) -> Result<i128, String> {
+    let tree = minimum_spanning_tree(points, metric);
+    let edge = tree.connecting_edge().ok_or("At least two junction boxes are needed")?;
+    Ok(points[edge.a][0] as i128 * points[edge.b][0] as i128)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::rng::Lcg;
+
     const POINTS: [[i64; 3]; 20] = [
         [162, 817, 812],
         [57, 618, 57],
@@ -139,11 +329,139 @@ mod tests {
 
     #[test]
     fn test_example_part_1() {
-        assert_eq!(part1(&POINTS, 10), 40);
+        assert_eq!(part1(&POINTS, 10, Metric::SquaredEuclidean), 40);
     }
 
     #[test]
     fn test_example_part_2() {
-        assert_eq!(part2(&POINTS), 25272);
+        assert_eq!(part2(&POINTS, Metric::SquaredEuclidean), Ok(25272));
+    }
+
+    #[test]
+    fn test_minimum_spanning_tree() {
+        let tree = minimum_spanning_tree(&POINTS, Metric::SquaredEuclidean);
+        assert_eq!(tree.edges.len(), POINTS.len() - 1);
+        assert!(tree.edges.windows(2).all(|w| w[0].weight <= w[1].weight));
+        assert_eq!(
+            tree.total_weight,
+            Some(tree.edges.iter().map(|edge| edge.weight).sum::<i128>())
+        );
+
+        // The closest pair is accepted first, and the last edge joins 216,146,977 and 117,168,530
+        assert_eq!(tree.edges[0], Edge { weight: 100427, a: 0, b: 19 });
+        let last = tree.connecting_edge().unwrap();
+        assert_eq!((last.a, last.b), (10, 12));
+    }
+
+    #[test]
+    fn test_circuits_after() {
+        let circuits = circuits_after(&POINTS, 10, Metric::SquaredEuclidean);
+        let sizes: Vec<usize> = circuits.iter().map(Circuit::size).collect();
+        assert_eq!(sizes, vec![5, 4, 2, 2, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(circuits.iter().map(|c| c.members.len()).sum::<usize>(), POINTS.len());
+
+        for circuit in &circuits {
+            for &member in &circuit.members {
+                for (axis, value) in POINTS[member].iter().enumerate() {
+                    assert!((circuit.min[axis]..=circuit.max[axis]).contains(value));
+                }
+            }
+        }
+
+        // Without connections every box is alone, with enough of them there is one circuit
+        assert_eq!(circuits_after(&POINTS, 0, Metric::SquaredEuclidean).len(), POINTS.len());
+        let single = circuits_after(&POINTS, usize::MAX, Metric::SquaredEuclidean);
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].min, [52, 29, 18]);
+        assert_eq!(single[0].max, [984, 993, 988]);
+    }
+
+    #[test]
+    fn test_single_point_has_no_connecting_edge() {
+        let tree = minimum_spanning_tree(&POINTS[..1], Metric::SquaredEuclidean);
+        assert!(tree.edges.is_empty());
+        assert_eq!(tree.total_weight, Some(0));
+        assert_eq!(tree.connecting_edge(), None);
+
+        let message = Err("At least two junction boxes are needed".to_string());
+        assert_eq!(part2(&POINTS[..1], Metric::SquaredEuclidean), message);
+        assert_eq!(part2::<3>(&[], Metric::SquaredEuclidean), message);
+    }
+
+    #[test]
+    fn test_parse_points() {
+        assert_eq!(parse_points::<3>("1,2,3\n\n-4, 5 ,6\n"), Ok(vec![[1, 2, 3], [-4, 5, 6]]));
+        assert_eq!(
+            parse_points::<3>("1,2,3\n4,5"),
+            Err("Line 2: expected 3 coordinates, found 2 (4,5)".to_string())
+        );
+        assert!(
+            parse_points::<3>("1,x,3").unwrap_err().starts_with("Line 1: invalid coordinate 'x'")
+        );
+    }
+
+    #[test]
+    fn test_matches_kruskal_on_clusters() {
+        // Far apart clusters of tightly packed boxes, with many equal distances and duplicates
+        let mut rng = Lcg::new(8);
+        let mut points: Vec<[i64; 3]> = vec![];
+        for cluster in 0..4 {
+            let base = [cluster * 1_000_000_000, -cluster * 7_000_000, 0];
+            for _ in 0..60 {
+                points.push(std::array::from_fn(|axis| base[axis] + rng.below(4) as i64));
+            }
+        }
+
+        for metric in [Metric::SquaredEuclidean, Metric::Manhattan, Metric::Chebyshev] {
+            let mut uf = UnionFind::new(points.len());
+            let kruskal: Vec<Edge> = ClosestPairs::new(&points, metric)
+                .filter(|&(_, a, b)| uf.union(a, b))
+                .map(|(weight, a, b)| Edge { weight, a, b })
+                .collect();
+            assert_eq!(minimum_spanning_tree(&points, metric).edges, kruskal);
+        }
+    }
+
+    #[test]
+    fn test_other_dimensions_and_metrics() {
+        // Two clusters on a line, joined last across the 3 -> 20 gap
+        let line: [[i64; 1]; 5] = [[0], [1], [3], [20], [21]];
+        assert_eq!(part2(&line, Metric::Manhattan), Ok(60));
+        let sizes: Vec<usize> =
+            circuits_after(&line, 3, Metric::Chebyshev).iter().map(Circuit::size).collect();
+        assert_eq!(sizes, vec![3, 2]);
+
+        let plane: [[i64; 2]; 4] = [[0, 0], [3, 3], [0, 5], [10, 0]];
+        // (0,0)-(3,3) is 6 apart in Manhattan but 3 in Chebyshev, vs 5 for (0,0)-(0,5)
+        let first = |metric| minimum_spanning_tree(&plane, metric).edges[0];
+        assert_eq!((first(Metric::Manhattan).a, first(Metric::Manhattan).b), (0, 2));
+        assert_eq!((first(Metric::Chebyshev).a, first(Metric::Chebyshev).b), (0, 1));
+    }
+
+    #[test]
+    fn test_huge_coordinates() {
+        let q = i64::MAX / 4;
+        let points = [[q, q, q], [-q, -q, -q], [q - 3, q, q - 4], [-q, -q + 1, -q]];
+        assert_eq!(check_range(&points, Metric::SquaredEuclidean), Ok(()));
+
+        let tree = minimum_spanning_tree(&points, Metric::SquaredEuclidean);
+        assert_eq!(tree.edges[0].weight, 1);
+        assert_eq!(tree.edges[1].weight, 25);
+        let span = 2 * q as i128;
+        let expected = (span - 3).pow(2) + (span - 1).pow(2) + (span - 4).pow(2);
+        assert_eq!(tree.connecting_edge().unwrap().weight, expected);
+        assert_eq!(tree.total_weight, Some(expected + 26));
+        // Joining (q - 3, q, q - 4) to (-q, -q + 1, -q)
+        assert_eq!(part2(&points, Metric::SquaredEuclidean), Ok(-(q as i128 - 3) * q as i128));
+    }
+
+    #[test]
+    fn test_out_of_range_coordinates() {
+        let points = [[i64::MAX, 0, 0], [i64::MIN, 0, 0]];
+        assert_eq!(
+            check_range(&points, Metric::SquaredEuclidean),
+            Err("Coordinates span too wide a range for the SquaredEuclidean metric".to_string())
+        );
+        assert_eq!(check_range(&points, Metric::Manhattan), Ok(()));
     }
 }
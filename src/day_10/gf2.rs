@@ -0,0 +1,143 @@
+use crate::utils::bitmask::BitMask;
+
+/// Largest null space `min_weight` walks: 2²⁴ combinations still take well under a second.
+const MAX_FREE_BUTTONS: usize = 24;
+
+/// Solution space of `buttons · x = target` over GF(2): every `particular ⊕ (any XOR of
+/// null-space vectors)` toggles exactly the target lights.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gf2Solution {
+    pub particular: BitMask,
+    pub null_space: Vec<BitMask>,
+}
+
+/// Solves the system whose columns are `buttons`, or `None` if `target` is unreachable.
+pub fn solve(buttons: &[BitMask], target: &BitMask) -> Option<Gf2Solution> {
+    let n_lights = buttons.iter().chain([target]).map(BitMask::len).max().unwrap_or(0);
+
+    // Row per light: the buttons toggling it, and whether it must end up on
+    let mut rows: Vec<(BitMask, bool)> = (0..n_lights)
+        .map(|light| {
+            let mut toggled_by = BitMask::default();
+            for (button, mask) in buttons.iter().enumerate() {
+                if mask.get(light) {
+                    toggled_by.set(button);
+                }
+            }
+            (toggled_by, target.get(light))
+        })
+        .collect();
+
+    let mut pivots: Vec<usize> = Vec::new();
+    for col in 0..buttons.len() {
+        let pivot = pivots.len();
+        let Some(found) = (pivot..rows.len()).find(|&row| rows[row].0.get(col)) else {
+            continue;
+        };
+        rows.swap(pivot, found);
+        pivots.push(col);
+
+        let (pivot_mask, pivot_rhs) = rows[pivot].clone();
+        for (row, (mask, rhs)) in rows.iter_mut().enumerate() {
+            if row != pivot && mask.get(col) {
+                *mask ^= &pivot_mask;
+                *rhs ^= pivot_rhs;
+            }
+        }
+    }
+
+    // A light no button combination can reach still needs toggling
+    if rows[pivots.len()..].iter().any(|(_, rhs)| *rhs) {
+        return None;
+    }
+
+    let mut particular = BitMask::default();
+    for (&col, (_, rhs)) in pivots.iter().zip(&rows) {
+        if *rhs {
+            particular.set(col);
+        }
+    }
+
+    let null_space = (0..buttons.len())
+        .filter(|col| !pivots.contains(col))
+        .map(|free| {
+            let mut vector = BitMask::default();
+            vector.set(free);
+            for (&col, (mask, _)) in pivots.iter().zip(&rows) {
+                if mask.get(free) {
+                    vector.set(col);
+                }
+            }
+            vector
+        })
+        .collect();
+
+    Some(Gf2Solution { particular, null_space })
+}
+
+impl Gf2Solution {
+    /// Solution pressing the fewest buttons, found by walking every null-space combination in
+    /// Gray-code order so each step costs a single XOR. Fails when there are more than
+    /// `MAX_FREE_BUTTONS` free buttons.
+    pub fn min_weight(&self) -> Result<BitMask, String> {
+        if self.null_space.len() > MAX_FREE_BUTTONS {
+            return Err(format!(
+                "has too many free buttons ({}) to enumerate",
+                self.null_space.len()
+            ));
+        }
+        let mut current = self.particular.clone();
+        let mut best = current.clone();
+        for step in 1u64..1 << self.null_space.len() {
+            current ^= &self.null_space[step.trailing_zeros() as usize];
+            if current.count_ones() < best.count_ones() {
+                best = current.clone();
+            }
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_solution() {
+        let buttons = [BitMask::from_indices(&[0]), BitMask::from_indices(&[0, 1])];
+        let solution = solve(&buttons, &BitMask::from_indices(&[1])).unwrap();
+        assert_eq!(solution.particular, BitMask::from_indices(&[0, 1]));
+        assert!(solution.null_space.is_empty());
+    }
+
+    #[test]
+    fn test_min_weight_uses_null_space() {
+        // Pressing {0, 1} or {2} both toggle lights 0 and 1
+        let buttons = [
+            BitMask::from_indices(&[0]),
+            BitMask::from_indices(&[1]),
+            BitMask::from_indices(&[0, 1]),
+        ];
+        let solution = solve(&buttons, &BitMask::from_indices(&[0, 1])).unwrap();
+        assert_eq!(solution.null_space.len(), 1);
+        assert_eq!(solution.min_weight(), Ok(BitMask::from_indices(&[2])));
+    }
+
+    #[test]
+    fn test_too_many_free_buttons() {
+        // Every copy after the first is free
+        let buttons = vec![BitMask::from_indices(&[0]); MAX_FREE_BUTTONS + 2];
+        let solution = solve(&buttons, &BitMask::from_indices(&[0])).unwrap();
+        assert_eq!(solution.null_space.len(), MAX_FREE_BUTTONS + 1);
+        assert_eq!(
+            solution.min_weight(),
+            Err(format!("has too many free buttons ({}) to enumerate", MAX_FREE_BUTTONS + 1))
+        );
+    }
+
+    #[test]
+    fn test_unsolvable() {
+        let buttons = [BitMask::from_indices(&[0, 1])];
+        assert_eq!(solve(&buttons, &BitMask::from_indices(&[0])), None);
+    }
+}
@@ -1,38 +1,17 @@
 use indicatif::{ProgressBar, ProgressStyle};
-use std::{fs, ops::BitXor};
+use std::fs;
 
-use itertools::Itertools;
 use rayon::iter::{ParallelBridge, ParallelIterator};
 use regex::Regex;
 
-const EPSILON: f64 = 1e-9;
+use crate::utils::{bitmask::BitMask, ilp::IntegerProgram, matrix::Matrix, rational::Rational};
 
-#[derive(Debug, Default, PartialEq, Clone, Copy)]
-struct BitMask(u64);
-
-impl BitMask {
-    fn from_string(pattern: String) -> Option<BitMask> {
-        Some(BitMask(pattern.chars().rev().fold(0, |acc, c| (acc << 1) | (c == '#') as u64)))
-    }
-
-    fn from_indices(indices: &[u64]) -> BitMask {
-        BitMask(indices.iter().map(|&i| 1u64 << i).sum())
-    }
-}
-
-impl BitXor for BitMask {
-    type Output = BitMask;
-
-    fn bitxor(self, other: BitMask) -> BitMask {
-        BitMask(self.0 ^ other.0)
-    }
-}
+mod gf2;
 
 #[derive(Debug, Clone)]
 struct Puzzle {
     target_indicators: BitMask,
     buttons: Vec<BitMask>,
-    #[allow(dead_code)]
     joltages: Vec<usize>,
 }
 
@@ -42,13 +21,12 @@ fn parse_line(
     re_buttons: &Regex,
     re_joltages: &Regex,
 ) -> Option<Puzzle> {
-    let target_indicators =
-        BitMask::from_string(re_target_indicators.captures(line)?[1].to_string())?;
+    let target_indicators = BitMask::from_string(&re_target_indicators.captures(line)?[1])?;
 
     let buttons = re_buttons
         .captures_iter(line)
         .map(|c| {
-            let indices: Vec<u64> = c[1].split(',').map(|n| n.trim().parse().unwrap()).collect();
+            let indices: Vec<usize> = c[1].split(',').map(|n| n.trim().parse().unwrap()).collect();
             BitMask::from_indices(&indices)
         })
         .collect();
@@ -82,158 +60,59 @@ pub fn run(part: u8, is_test: bool) {
         },
     };
 
-    println!("Day 10 Part {}: {}", part, result);
+    match result {
+        Ok(result) => println!("Day 10 Part {}: {}", part, result),
+        Err(err) => println!("{}", err),
+    }
 }
 
-fn part1(puzzles: &[Puzzle]) -> usize {
+/// Indices of the fewest buttons to press so that exactly the target indicators light up.
+fn indicator_presses(puzzle: &Puzzle) -> Result<Vec<usize>, String> {
+    let solution = gf2::solve(&puzzle.buttons, &puzzle.target_indicators)
+        .ok_or(format!("cannot reach {}", puzzle.target_indicators))?;
+    Ok(solution.min_weight()?.iter_ones().collect())
+}
+
+fn part1(puzzles: &[Puzzle]) -> Result<usize, String> {
     puzzles
         .iter()
-        .filter_map(|puzzle| {
-            for r in 1..=puzzle.buttons.len() {
-                for combo in puzzle.buttons.iter().combinations(r) {
-                    let mut state = BitMask::default();
-                    for cons in combo {
-                        state = state ^ *cons;
-                    }
-                    if state == puzzle.target_indicators {
-                        return Some(r);
-                    }
-                }
-            }
-            None
+        .enumerate()
+        .map(|(idx, puzzle)| {
+            indicator_presses(puzzle)
+                .map(|presses| presses.len())
+                .map_err(|err| format!("Machine {} {}", idx + 1, err))
         })
         .sum()
 }
 
-struct Matrix {
-    data: Vec<Vec<f64>>,
-    rows: usize,
-    cols: usize,
-    dependent: Vec<usize>,   // Columns with pivots
-    independent: Vec<usize>, // Free variables
-}
-
-impl Matrix {
-    fn from_puzzle(puzzle: &Puzzle) -> Self {
-        let rows = puzzle.joltages.len();
-        let cols = puzzle.buttons.len();
-
-        // Build augmented matrix [A | b]
-        let mut data = vec![vec![0.0; cols + 1]; rows];
-        for (col, &mask) in puzzle.buttons.iter().enumerate() {
-            for (row, data_row) in data.iter_mut().enumerate().take(rows) {
-                if (mask.0 >> row) & 1 == 1 {
-                    data_row[col] = 1.0;
-                }
-            }
-        }
-        for (row, &val) in puzzle.joltages.iter().enumerate() {
-            data[row][cols] = val as f64;
-        }
-
-        let mut matrix =
-            Matrix { data, rows, cols, dependent: Vec::new(), independent: Vec::new() };
-
-        matrix.gaussian_elimination();
-        matrix
-    }
-
-    fn gaussian_elimination(&mut self) {
-        let mut pivot = 0;
-
-        for col in 0..self.cols {
-            // Find best pivot row (largest absolute value)
-            let best = (pivot..self.rows).max_by(|&a, &b| {
-                self.data[a][col].abs().partial_cmp(&self.data[b][col].abs()).unwrap()
-            });
+fn build_system(puzzle: &Puzzle) -> (Matrix, Vec<Rational>) {
+    let rows = puzzle.joltages.len();
+    let cols = puzzle.buttons.len();
 
-            let Some(best_row) = best else { continue };
-
-            // If best value is ~0, this column is a free variable
-            if self.data[best_row][col].abs() < EPSILON {
-                self.independent.push(col);
-                continue;
-            }
-
-            // Swap and mark as dependent
-            self.data.swap(pivot, best_row);
-            self.dependent.push(col);
-
-            // Normalize pivot row
-            let pivot_val = self.data[pivot][col];
-            for val in &mut self.data[pivot][col..=self.cols] {
-                *val /= pivot_val;
-            }
-
-            // Eliminate column in all other rows
-            for row in 0..self.rows {
-                if row != pivot {
-                    let factor = self.data[row][col];
-                    if factor.abs() > EPSILON {
-                        for c in col..=self.cols {
-                            self.data[row][c] -= factor * self.data[pivot][c];
-                        }
-                    }
-                }
-            }
-
-            pivot += 1;
+    // Column `col` of A holds the counters incremented by button `col`
+    let mut a = Matrix::new(rows, cols);
+    for (col, mask) in puzzle.buttons.iter().enumerate() {
+        for row in mask.iter_ones() {
+            a[(row, col)] = Rational::ONE;
         }
-
-        // Remaining columns are free variables
-        self.independent.extend(pivot..self.cols);
     }
+    let b = puzzle.joltages.iter().map(|&val| Rational::from(val)).collect();
 
-    // Check if independent variable assignment gives valid solution
-    fn valid(&self, free_vals: &[usize]) -> Option<usize> {
-        let mut total = free_vals.iter().sum::<usize>();
-
-        // Calculate each dependent variable
-        for (row, _) in self.dependent.iter().enumerate() {
-            let val = self.data[row][self.cols]
-                - self
-                    .independent
-                    .iter()
-                    .zip(free_vals)
-                    .map(|(&col, &v)| self.data[row][col] * v as f64)
-                    .sum::<f64>();
-
-            // Must be non-negative integer
-            if val < -EPSILON {
-                return None;
-            }
-            let rounded = val.round();
-            if (val - rounded).abs() > EPSILON {
-                return None;
-            }
-
-            total += rounded as usize;
-        }
-
-        Some(total)
-    }
+    (a, b)
 }
 
-fn search(matrix: &Matrix, idx: usize, vals: &mut [usize], best: &mut usize, max: usize) {
-    if idx == matrix.independent.len() {
-        if let Some(total) = matrix.valid(vals) {
-            *best = (*best).min(total);
-        }
-        return;
-    }
-
-    let current: usize = vals[..idx].iter().sum();
-    for v in 0..max {
-        if current + v >= *best {
-            break;
-        } // Prune
-        vals[idx] = v;
-        search(matrix, idx + 1, vals, best, max);
-    }
+/// Press count of each button reaching the joltage targets with the fewest presses overall.
+fn min_presses(puzzle: &Puzzle) -> Result<Vec<usize>, String> {
+    let (a, b) = build_system(puzzle);
+    let costs = vec![Rational::ONE; a.cols()];
+    let solution = IntegerProgram::new(&a, &b, &costs).minimize()?.ok_or_else(|| {
+        let joltages: Vec<String> = puzzle.joltages.iter().map(usize::to_string).collect();
+        format!("cannot reach joltages {{{}}}", joltages.join(","))
+    })?;
+    Ok(solution.x.into_iter().map(|presses| presses as usize).collect())
 }
 
-fn part2(puzzles: &[Puzzle]) -> usize {
-    // Heavily inspired by https://gist.github.com/icub3d/16eea2a8b4a94d193a148fef908779a9
+fn part2(puzzles: &[Puzzle]) -> Result<usize, String> {
     let pb = ProgressBar::new(puzzles.len() as u64);
     pb.set_style(
         ProgressStyle::default_bar()
@@ -242,20 +121,16 @@ fn part2(puzzles: &[Puzzle]) -> usize {
             .progress_chars("=>-"),
     );
 
-    let result: usize = puzzles
+    let result: Result<usize, String> = puzzles
         .iter()
+        .enumerate()
         .par_bridge()
-        .map(|puzzle| {
-            let matrix = Matrix::from_puzzle(puzzle);
-
-            // Much better bound: sum of joltages instead of max
-            let max = puzzle.joltages.iter().sum::<usize>() / matrix.independent.len().max(1);
-
-            let mut best = usize::MAX;
-            let mut vals = vec![0; matrix.independent.len()];
-            search(&matrix, 0, &mut vals, &mut best, max);
+        .map(|(idx, puzzle)| {
+            let presses = min_presses(puzzle);
             pb.inc(1);
-            best
+            presses
+                .map(|presses| presses.iter().sum::<usize>())
+                .map_err(|err| format!("Machine {} {}", idx + 1, err))
         })
         .sum();
 
@@ -269,7 +144,7 @@ mod tests {
     fn get_puzzles() -> Vec<Puzzle> {
         [
             Puzzle {
-                target_indicators: BitMask::from_string(".##.".to_string()).unwrap(),
+                target_indicators: BitMask::from_string(".##.").unwrap(),
                 buttons: vec![
                     BitMask::from_indices(&[3]),
                     BitMask::from_indices(&[1, 3]),
@@ -281,7 +156,7 @@ mod tests {
                 joltages: vec![3, 5, 4, 7],
             },
             Puzzle {
-                target_indicators: BitMask::from_string("...#..".to_string()).unwrap(),
+                target_indicators: BitMask::from_string("...#..").unwrap(),
                 buttons: vec![
                     BitMask::from_indices(&[0, 2, 3, 4]),
                     BitMask::from_indices(&[2, 3]),
@@ -292,7 +167,7 @@ mod tests {
                 joltages: vec![7, 5, 12, 7, 2],
             },
             Puzzle {
-                target_indicators: BitMask::from_string(".###.#.".to_string()).unwrap(),
+                target_indicators: BitMask::from_string(".###.#.").unwrap(),
                 buttons: vec![
                     BitMask::from_indices(&[0, 1, 2, 3, 4]),
                     BitMask::from_indices(&[0, 3, 4]),
@@ -308,12 +183,85 @@ mod tests {
     #[test]
     fn test_example_part1() {
         let puzzles = get_puzzles();
-        assert_eq!(part1(&puzzles), 7); // 2 + 3 + 2 = 7
+        assert_eq!(part1(&puzzles), Ok(7)); // 2 + 3 + 2 = 7
+    }
+
+    #[test]
+    fn test_indicator_presses() {
+        let puzzles = get_puzzles();
+        for (puzzle, expected) in puzzles.iter().zip([2, 3, 2]) {
+            let presses = indicator_presses(puzzle).unwrap();
+            assert_eq!(presses.len(), expected);
+            let state =
+                presses.iter().fold(BitMask::default(), |acc, &b| &acc ^ &puzzle.buttons[b]);
+            assert_eq!(state, puzzle.target_indicators);
+        }
+    }
+
+    #[test]
+    fn test_more_than_64_lights() {
+        let re_pattern = Regex::new(r"\[([#.]+)\]").unwrap();
+        let re_constraint = Regex::new(r"\(([^)]+)\)").unwrap();
+        let re_values = Regex::new(r"\{([^}]+)\}").unwrap();
+        let line = format!("[{}#] (0,69) (0) (69) {{1,2}}", ".".repeat(69));
+
+        let puzzle = parse_line(&line, &re_pattern, &re_constraint, &re_values).unwrap();
+        assert_eq!(puzzle.target_indicators.iter_ones().collect::<Vec<_>>(), vec![69]);
+        assert_eq!(puzzle.buttons[0], BitMask::from_indices(&[0, 69]));
+        assert_eq!(part1(&[puzzle]), Ok(1));
+    }
+
+    #[test]
+    fn test_unsolvable_machine() {
+        let mut puzzles = get_puzzles();
+        puzzles[1].buttons = vec![BitMask::from_indices(&[0, 1])];
+        assert_eq!(part1(&puzzles), Err("Machine 2 cannot reach [...#..]".to_string()));
+    }
+
+    #[test]
+    fn test_too_many_free_buttons() {
+        let mut puzzles = get_puzzles();
+        puzzles[2].buttons = vec![BitMask::from_indices(&[1, 2, 3, 5]); 70];
+        assert_eq!(
+            part1(&puzzles),
+            Err("Machine 3 has too many free buttons (69) to enumerate".to_string())
+        );
     }
 
     #[test]
     fn test_example_part2() {
         let puzzles = get_puzzles();
-        assert_eq!(part2(&puzzles), 33); // 10 + 12 + 11 = 33
+        assert_eq!(part2(&puzzles), Ok(33)); // 10 + 12 + 11 = 33
+    }
+
+    #[test]
+    fn test_unreachable_joltages() {
+        let mut puzzles = get_puzzles();
+        // The only button raises every counter at once, so they can never differ
+        puzzles[1].buttons = vec![BitMask::from_indices(&[0, 1, 2, 3, 4])];
+        assert_eq!(
+            part2(&puzzles),
+            Err("Machine 2 cannot reach joltages {7,5,12,7,2}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_min_presses_vector() {
+        let puzzles = get_puzzles();
+        for (puzzle, expected) in puzzles.iter().zip([10, 12, 11]) {
+            let presses = min_presses(puzzle).unwrap();
+            assert_eq!(presses.iter().sum::<usize>(), expected);
+
+            // Replaying the presses reaches every joltage target exactly
+            let mut joltages = vec![0; puzzle.joltages.len()];
+            for (button, &count) in puzzle.buttons.iter().zip(&presses) {
+                for (counter, joltage) in joltages.iter_mut().enumerate() {
+                    if button.get(counter) {
+                        *joltage += count;
+                    }
+                }
+            }
+            assert_eq!(joltages, puzzle.joltages);
+        }
     }
 }
@@ -9,6 +9,7 @@ mod day_08;
 mod day_09;
 mod day_10;
 mod day_11;
+mod utils;
 use std::env;
 
 fn main() {
@@ -19,6 +20,37 @@ fn main() {
         println!("Example: {} 1      # runs day 1 part 1", args[0]);
         println!("Example: {} 1.2    # runs day 1 part 2", args[0]);
         println!("Example: {} 1 --test", args[0]);
+        println!();
+        println!("Day options:");
+        println!("  1: --dial-size <n>    numbers on the dial (default 100)");
+        println!("     --start <n>        starting position (default 50)");
+        println!("     --mark <n>         position counted by the password (default 0)");
+        println!("     --trace            print the position after each rotation");
+        println!(
+            "  2: --rule <rule>      exactly:<k>, at-least:<k>, at-most:<k> or periods:<p,...>"
+        );
+        println!("     --radix <b>        base the IDs are written in (default 10)");
+        println!("     --count            also print the number of invalid IDs");
+        println!("     --merge            merge overlapping ranges so IDs are counted once");
+        println!(
+            "  3: --batteries <n>    batteries turned on per bank (default 2, or 12 in part 2)"
+        );
+        println!("     --explain          highlight the chosen batteries of each bank");
+        println!("  4: --threshold <n>    neighbours below which a roll is accessible (default 4)");
+        println!("     --neighborhood <n> moore (default), von-neumann or offsets like -1,0;1,0");
+        println!("     --roll <c>         symbol of a paper roll (default @)");
+        println!("     --toroidal         wrap neighbourhoods around the grid edges");
+        println!("     --waves            print the rolls removed and remaining after each wave");
+        println!(
+            "     --export <path>    write the grid annotated with removal waves (.ppm or text)"
+        );
+        println!("  5: --matches          print the fresh ranges containing each ingredient");
+        println!("     --stream           read ingredients line by line instead of loading them");
+        println!("  8: --connections <k>  connections made in part 1 (default 1000)");
+        println!("     --metric <name>    euclidean (default), manhattan or chebyshev");
+        println!("     --histogram        print the circuit sizes after k connections");
+        println!("     --stream           print the number of circuits after each connection");
+        println!("     --tree             print the minimum spanning tree edges and total weight");
         return;
     }
 
@@ -34,14 +66,14 @@ fn main() {
     };
 
     match day {
-        "1" => day_01::run(part, is_test),
-        "2" => day_02::run(part, is_test),
-        "3" => day_03::run(part, is_test),
-        "4" => day_04::run(part, is_test),
-        "5" => day_05::run(part, is_test),
+        "1" => day_01::run(part, is_test, &args[2..]),
+        "2" => day_02::run(part, is_test, &args[2..]),
+        "3" => day_03::run(part, is_test, &args[2..]),
+        "4" => day_04::run(part, is_test, &args[2..]),
+        "5" => day_05::run(part, is_test, &args[2..]),
         "6" => day_06::run(part, is_test),
         "7" => day_07::run(part, is_test),
-        "8" => day_08::run(part, is_test),
+        "8" => day_08::run(part, is_test, &args[2..]),
         "9" => day_09::run(part, is_test),
         "10" => day_10::run(part, is_test),
         "11" => day_11::run(part, is_test),
@@ -0,0 +1,149 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign},
+};
+
+/// Decimal limbs hold 9 digits each, so converting from and to digit strings needs no division.
+const BASE: u64 = 1_000_000_000;
+
+/// Unsigned integer of arbitrary size, stored as base 10⁹ limbs, least significant first and
+/// without leading zero limbs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint::default()
+    }
+
+    /// Builds the number whose decimal digits (each in `0..=9`) are given most significant first.
+    pub fn from_digits(digits: &[u8]) -> Self {
+        let limbs = digits
+            .rchunks(9)
+            .map(|chunk| chunk.iter().fold(0, |limb, &digit| limb * 10 + digit as u32))
+            .collect();
+        let mut number = BigUint { limbs };
+        number.trim();
+        number
+    }
+
+    /// The value as a `u128`, if it fits.
+    #[cfg(test)]
+    pub fn to_u128(&self) -> Option<u128> {
+        self.limbs.iter().rev().try_fold(0u128, |value, &limb| {
+            value.checked_mul(BASE as u128)?.checked_add(limb as u128)
+        })
+    }
+
+    fn trim(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(mut value: u128) -> Self {
+        let mut limbs = Vec::new();
+        while value > 0 {
+            limbs.push((value % BASE as u128) as u32);
+            value /= BASE as u128;
+        }
+        BigUint { limbs }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        BigUint::from(value as u128)
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, other: &BigUint) {
+        if self.limbs.len() < other.limbs.len() {
+            self.limbs.resize(other.limbs.len(), 0);
+        }
+        let mut carry = 0;
+        for (idx, limb) in self.limbs.iter_mut().enumerate() {
+            let sum = *limb as u64 + other.limbs.get(idx).copied().unwrap_or(0) as u64 + carry;
+            *limb = (sum % BASE) as u32;
+            carry = sum / BASE;
+        }
+        if carry > 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+
+    fn add(mut self, other: BigUint) -> BigUint {
+        self += &other;
+        self
+    }
+}
+
+impl Sum for BigUint {
+    fn sum<I: Iterator<Item = BigUint>>(iter: I) -> BigUint {
+        iter.fold(BigUint::zero(), Add::add)
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some((top, rest)) = self.limbs.split_last() else {
+            return write!(f, "0");
+        };
+        write!(f, "{}", top)?;
+        for limb in rest.iter().rev() {
+            write!(f, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digits_round_trip() {
+        let digits: Vec<u8> = "0012345678901234567890123456789".bytes().map(|b| b - b'0').collect();
+        let number = BigUint::from_digits(&digits);
+        assert_eq!(number.to_string(), "12345678901234567890123456789");
+        assert_eq!(number.to_u128(), Some(12345678901234567890123456789));
+        assert_eq!(BigUint::from_digits(&[0, 0]), BigUint::zero());
+        assert_eq!(BigUint::zero().to_string(), "0");
+    }
+
+    #[test]
+    fn test_sum_beyond_u128() {
+        let max = BigUint::from(u128::MAX);
+        assert_eq!(max.to_u128(), Some(u128::MAX));
+        let doubled: BigUint = [max.clone(), max.clone()].into_iter().sum();
+        assert_eq!(doubled.to_string(), "680564733841876926926749214863536422910");
+        assert_eq!(doubled.to_u128(), None);
+        assert!(doubled > max && BigUint::from(999_999_999u64) < BigUint::from(1_000_000_000u64));
+    }
+}
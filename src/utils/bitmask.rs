@@ -0,0 +1,213 @@
+use std::{
+    fmt, iter,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign},
+    slice,
+};
+
+/// Bits live inline while they fit in one word, and spill to the heap past that.
+#[derive(Debug, Clone)]
+enum Words {
+    Inline(u64),
+    Heap(Vec<u64>),
+}
+
+/// Bitset of arbitrary width. `len` is the number of tracked positions (e.g. lights on a
+/// machine) and only matters for display: equality and the bitwise operators look at set bits.
+#[derive(Debug, Clone)]
+pub struct BitMask {
+    words: Words,
+    len: usize,
+}
+
+impl Default for BitMask {
+    fn default() -> Self {
+        BitMask { words: Words::Inline(0), len: 0 }
+    }
+}
+
+impl BitMask {
+    pub fn new(len: usize) -> Self {
+        let n_words = len.div_ceil(64);
+        let words = if n_words <= 1 { Words::Inline(0) } else { Words::Heap(vec![0; n_words]) };
+        BitMask { words, len }
+    }
+
+    /// Parses the puzzle's `.##.` notation (without brackets), `#` being a set bit.
+    pub fn from_string(pattern: &str) -> Option<BitMask> {
+        let mut mask = BitMask::new(pattern.chars().count());
+        for (idx, c) in pattern.chars().enumerate() {
+            match c {
+                '#' => mask.set(idx),
+                '.' => {},
+                _ => return None,
+            }
+        }
+        Some(mask)
+    }
+
+    pub fn from_indices(indices: &[usize]) -> BitMask {
+        let mut mask = BitMask::default();
+        for &idx in indices {
+            mask.set(idx);
+        }
+        mask
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn words(&self) -> &[u64] {
+        match &self.words {
+            Words::Inline(word) => slice::from_ref(word),
+            Words::Heap(words) => words,
+        }
+    }
+
+    fn words_mut(&mut self, n_words: usize) -> &mut [u64] {
+        if let Words::Inline(word) = self.words
+            && n_words > 1
+        {
+            self.words = Words::Heap(vec![word]);
+        }
+        match &mut self.words {
+            Words::Inline(word) => slice::from_mut(word),
+            Words::Heap(words) => {
+                if words.len() < n_words {
+                    words.resize(n_words, 0);
+                }
+                words
+            },
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> bool {
+        self.words().get(idx / 64).is_some_and(|word| (word >> (idx % 64)) & 1 == 1)
+    }
+
+    pub fn set(&mut self, idx: usize) {
+        self.words_mut(idx / 64 + 1)[idx / 64] |= 1 << (idx % 64);
+        self.len = self.len.max(idx + 1);
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words().iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Indices of the set bits, in increasing order.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words().iter().enumerate().flat_map(|(w, &word)| {
+            let mut remaining = word;
+            iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let bit = remaining.trailing_zeros() as usize;
+                remaining &= remaining - 1;
+                Some(w * 64 + bit)
+            })
+        })
+    }
+
+    fn combine(&mut self, other: &BitMask, op: impl Fn(u64, u64) -> u64) {
+        let n_words = self.words().len().max(other.words().len());
+        let words = self.words_mut(n_words);
+        for (idx, word) in words.iter_mut().enumerate() {
+            *word = op(*word, other.words().get(idx).copied().unwrap_or(0));
+        }
+        self.len = self.len.max(other.len);
+    }
+}
+
+impl PartialEq for BitMask {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.words(), other.words());
+        (0..a.len().max(b.len()))
+            .all(|idx| a.get(idx).copied().unwrap_or(0) == b.get(idx).copied().unwrap_or(0))
+    }
+}
+
+impl Eq for BitMask {}
+
+macro_rules! impl_bit_op {
+    ($op:ident, $method:ident, $op_assign:ident, $method_assign:ident, $f:expr) => {
+        impl $op_assign<&BitMask> for BitMask {
+            fn $method_assign(&mut self, other: &BitMask) {
+                self.combine(other, $f);
+            }
+        }
+
+        impl $op<&BitMask> for &BitMask {
+            type Output = BitMask;
+
+            fn $method(self, other: &BitMask) -> BitMask {
+                let mut result = self.clone();
+                result.$method_assign(other);
+                result
+            }
+        }
+
+        impl $op for BitMask {
+            type Output = BitMask;
+
+            fn $method(mut self, other: BitMask) -> BitMask {
+                self.$method_assign(&other);
+                self
+            }
+        }
+    };
+}
+
+impl_bit_op!(BitXor, bitxor, BitXorAssign, bitxor_assign, |a, b| a ^ b);
+impl_bit_op!(BitAnd, bitand, BitAndAssign, bitand_assign, |a, b| a & b);
+impl_bit_op!(BitOr, bitor, BitOrAssign, bitor_assign, |a, b| a | b);
+
+impl fmt::Display for BitMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lights: String =
+            (0..self.len).map(|idx| if self.get(idx) { '#' } else { '.' }).collect();
+        write!(f, "[{}]", lights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mask = BitMask::from_string(".##.#").unwrap();
+        assert_eq!(mask.to_string(), "[.##.#]");
+        assert_eq!(mask, BitMask::from_indices(&[1, 2, 4]));
+        assert_eq!(BitMask::from_string(".x"), None);
+    }
+
+    #[test]
+    fn test_wide_masks() {
+        let wide = BitMask::from_indices(&[3, 64, 200]);
+        assert!(wide.get(200) && wide.get(64) && !wide.get(65));
+        assert_eq!(wide.iter_ones().collect::<Vec<_>>(), vec![3, 64, 200]);
+        assert_eq!(wide.count_ones(), 3);
+
+        let pattern = format!("{}#", ".".repeat(99));
+        let mask = BitMask::from_string(&pattern).unwrap();
+        assert_eq!(mask.iter_ones().collect::<Vec<_>>(), vec![99]);
+        assert_eq!(mask.to_string(), format!("[{}]", pattern));
+    }
+
+    #[test]
+    fn test_operators_across_widths() {
+        let narrow = BitMask::from_indices(&[0, 1]);
+        let wide = BitMask::from_indices(&[1, 100]);
+        assert_eq!(&narrow ^ &wide, BitMask::from_indices(&[0, 100]));
+        assert_eq!(&narrow & &wide, BitMask::from_indices(&[1]));
+        assert_eq!(&narrow | &wide, BitMask::from_indices(&[0, 1, 100]));
+
+        // Clearing the high bits compares equal to a narrow mask
+        assert_eq!(&wide ^ &BitMask::from_indices(&[100]), BitMask::from_indices(&[1]));
+    }
+}
@@ -0,0 +1,49 @@
+use std::{fmt::Display, str::FromStr};
+
+/// Whether `flag` was passed on the command line.
+pub fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
+
+/// Parses the value following `flag` (e.g. `--connections 10`), if the flag was passed.
+pub fn flag_value<T>(args: &[String], flag: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let Some(position) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    let value = args.get(position + 1).ok_or(format!("Missing value for {}", flag))?;
+    value
+        .parse()
+        .map(Some)
+        .map_err(|err| format!("Invalid value for {} ({}): {}", flag, value, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_flags() {
+        let args = args(&["--test", "--connections", "10"]);
+        assert!(has_flag(&args, "--test"));
+        assert!(!has_flag(&args, "--stream"));
+        assert_eq!(flag_value::<usize>(&args, "--connections"), Ok(Some(10)));
+        assert_eq!(flag_value::<usize>(&args, "--other"), Ok(None));
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        assert_eq!(
+            flag_value::<usize>(&args(&["--connections"]), "--connections"),
+            Err("Missing value for --connections".to_string())
+        );
+        assert!(flag_value::<usize>(&args(&["--connections", "ten"]), "--connections").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use super::{
+    matrix::{Matrix, Parameterization},
+    rational::Rational,
+    simplex::{self, LpOutcome},
+};
+
+/// Subproblems `minimize` solves before giving up. Branching always ends on a bounded region,
+/// so only unbounded ones need this many; day 10 machines take at most a few dozen.
+const MAX_NODES: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IlpSolution {
+    pub x: Vec<i128>,
+    pub objective: Rational,
+}
+
+/// A branch-and-bound subproblem: the original constraints plus per-variable bounds, along
+/// with the optimum of its LP relaxation.
+struct Node {
+    lower: Vec<i128>,
+    upper: Vec<Option<i128>>,
+    relaxed_x: Vec<Rational>,
+    relaxed_objective: Rational,
+}
+
+/// Integer linear program `min c·x` subject to `A·x = b`, `x ≥ 0` and `x` integral.
+pub struct IntegerProgram<'a> {
+    a: &'a Matrix,
+    b: &'a [Rational],
+    c: &'a [Rational],
+}
+
+impl<'a> IntegerProgram<'a> {
+    pub fn new(a: &'a Matrix, b: &'a [Rational], c: &'a [Rational]) -> Self {
+        assert_eq!(b.len(), a.rows(), "Right-hand side does not match the number of rows");
+        assert_eq!(c.len(), a.cols(), "Cost vector does not match the number of columns");
+        IntegerProgram { a, b, c }
+    }
+
+    /// Optimal integral solution found by best-first branch and bound, or `None` if no integral
+    /// point satisfies the constraints. Exact elimination first settles the systems that need
+    /// no branching: inconsistent ones, ones with a single solution, and ones where some
+    /// variable can never be integral. Fails once `MAX_NODES` subproblems have been solved,
+    /// since an unbounded variable can otherwise be branched on forever. Panics if the
+    /// relaxation is unbounded.
+    pub fn minimize(&self) -> Result<Option<IlpSolution>, String> {
+        let Some(solutions) = self.a.solve(self.b) else {
+            return Ok(None);
+        };
+        if solutions.free.is_empty() {
+            return Ok(self.candidate(&solutions.particular));
+        }
+        if never_integral(&solutions) {
+            return Ok(None);
+        }
+
+        let n = self.a.cols();
+        // With integral costs, every candidate's objective is integral too, so the LP bound
+        // can be rounded up before pruning
+        let integral_costs = self.c.iter().all(Rational::is_integer);
+        let prunes = |bound: &Rational, best: &Option<IlpSolution>| match best {
+            Some(best) if integral_costs => Rational::from(bound.ceil()) >= best.objective,
+            Some(best) => *bound >= best.objective,
+            None => false,
+        };
+
+        let mut nodes = vec![];
+        let mut queue = BinaryHeap::new();
+        let mut best: Option<IlpSolution> = None;
+
+        if let Some(root) = self.relax(vec![0; n], vec![None; n]) {
+            queue.push(Reverse((root.relaxed_objective, nodes.len())));
+            nodes.push(Some(root));
+        }
+
+        while let Some(Reverse((bound, idx))) = queue.pop() {
+            if prunes(&bound, &best) {
+                break;
+            }
+            if nodes.len() >= MAX_NODES {
+                return Err(format!("gave up after {} branch-and-bound nodes", MAX_NODES));
+            }
+            let node = nodes[idx].take().expect("Node is visited once");
+
+            // Branch on the first fractional variable, or accept an integral relaxation
+            let Some(var) = node.relaxed_x.iter().position(|v| !v.is_integer()) else {
+                let x = node.relaxed_x.iter().map(Rational::numer).collect();
+                best = Some(IlpSolution { x, objective: node.relaxed_objective });
+                continue;
+            };
+            let value = node.relaxed_x[var];
+
+            let mut upper = node.upper.clone();
+            upper[var] = Some(value.floor());
+            let mut lower = node.lower.clone();
+            lower[var] = value.ceil();
+
+            let children =
+                [self.relax(node.lower, upper), self.relax(lower, node.upper)].into_iter();
+            for child in children.flatten() {
+                if !prunes(&child.relaxed_objective, &best) {
+                    queue.push(Reverse((child.relaxed_objective, nodes.len())));
+                    nodes.push(Some(child));
+                }
+            }
+        }
+
+        Ok(best)
+    }
+
+    /// The solution at `x` if it is integral and non-negative.
+    fn candidate(&self, x: &[Rational]) -> Option<IlpSolution> {
+        if x.iter().any(|v| !v.is_integer() || *v < Rational::ZERO) {
+            return None;
+        }
+        let objective = x.iter().zip(self.c).fold(Rational::ZERO, |acc, (&x, &c)| acc + x * c);
+        Some(IlpSolution { x: x.iter().map(Rational::numer).collect(), objective })
+    }
+
+    /// Solves the LP relaxation with `lower ≤ x ≤ upper`, or `None` if it is infeasible.
+    ///
+    /// Lower bounds are removed by substituting `x = lower + x'`, and each upper bound becomes
+    /// an extra equality row `x'_j + s_j = upper_j - lower_j` with its own slack column.
+    fn relax(&self, lower: Vec<i128>, upper: Vec<Option<i128>>) -> Option<Node> {
+        let (m, n) = (self.a.rows(), self.a.cols());
+        let bounded: Vec<(usize, i128)> =
+            upper.iter().enumerate().filter_map(|(j, u)| Some((j, (*u)? - lower[j]))).collect();
+        if bounded.iter().any(|&(_, width)| width < 0) {
+            return None;
+        }
+
+        let mut a = Matrix::new(m + bounded.len(), n + bounded.len());
+        let mut b = Vec::with_capacity(m + bounded.len());
+        for i in 0..m {
+            let mut shifted = self.b[i];
+            for j in 0..n {
+                a[(i, j)] = self.a[(i, j)];
+                shifted = shifted - self.a[(i, j)] * Rational::from(lower[j]);
+            }
+            b.push(shifted);
+        }
+        for (k, &(j, width)) in bounded.iter().enumerate() {
+            a[(m + k, j)] = Rational::ONE;
+            a[(m + k, n + k)] = Rational::ONE;
+            b.push(Rational::from(width));
+        }
+        let mut c = self.c.to_vec();
+        c.resize(n + bounded.len(), Rational::ZERO);
+
+        let solution = match simplex::minimize(&a, &b, &c) {
+            LpOutcome::Optimal(solution) => solution,
+            LpOutcome::Infeasible => return None,
+            LpOutcome::Unbounded => panic!("Integer program has an unbounded relaxation"),
+        };
+
+        let relaxed_x: Vec<Rational> =
+            (0..n).map(|j| solution.x[j] + Rational::from(lower[j])).collect();
+        let relaxed_objective =
+            relaxed_x.iter().zip(self.c).fold(Rational::ZERO, |acc, (&x, &c)| acc + x * c);
+
+        Some(Node { lower, upper, relaxed_x, relaxed_objective })
+    }
+}
+
+/// Whether some pivot variable is fractional for every integral choice of the free variables:
+/// its constant term is fractional while all of its free coefficients are integers.
+fn never_integral(solutions: &Parameterization) -> bool {
+    solutions.pivots.iter().any(|&col| {
+        !solutions.particular[col].is_integer()
+            && solutions.basis.iter().all(|vector| vector[col].is_integer())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::matrix::vector;
+
+    #[test]
+    fn test_integral_relaxation() {
+        // x + y = 4 with y twice as expensive
+        let (a, b, c) = (Matrix::from_i64_rows(&[&[1, 1]]), vector(&[4]), vector(&[1, 2]));
+        let solution = IntegerProgram::new(&a, &b, &c).minimize().unwrap().unwrap();
+        assert_eq!(solution.x, vec![4, 0]);
+        assert_eq!(solution.objective, Rational::from(4i64));
+    }
+
+    #[test]
+    fn test_branching() {
+        // 2x + 2y + z = 5 forces z odd, while the relaxation prefers x = 5/2
+        let (a, b, c) = (Matrix::from_i64_rows(&[&[2, 2, 1]]), vector(&[5]), vector(&[1, 1, 3]));
+        let solution = IntegerProgram::new(&a, &b, &c).minimize().unwrap().unwrap();
+        assert_eq!(solution.objective, Rational::from(5i64));
+        assert_eq!(solution.x[2], 1);
+    }
+
+    #[test]
+    fn test_no_integral_point() {
+        // 2x = 3 has a fractional solution only
+        let (a, b, c) = (Matrix::from_i64_rows(&[&[2]]), vector(&[3]), vector(&[1]));
+        assert_eq!(IntegerProgram::new(&a, &b, &c).minimize(), Ok(None));
+    }
+
+    #[test]
+    fn test_unbounded_parity() {
+        // 2x - 2y = 1 has no integral point, yet x and y can grow without bound
+        let (a, b, c) = (Matrix::from_i64_rows(&[&[2, -2]]), vector(&[1]), vector(&[1, 1]));
+        assert_eq!(IntegerProgram::new(&a, &b, &c).minimize(), Ok(None));
+    }
+
+    #[test]
+    fn test_node_limit() {
+        // x - 2y + z = -4 and z - 3x = 1 combine into 4x - 2y = -5, a parity clash that no
+        // single row of the reduced system shows
+        let a = Matrix::from_i64_rows(&[&[1, -2, 1], &[-3, 0, 1]]);
+        let (b, c) = (vector(&[-4, 1]), vector(&[1, 1, 1]));
+        assert_eq!(
+            IntegerProgram::new(&a, &b, &c).minimize(),
+            Err(format!("gave up after {} branch-and-bound nodes", MAX_NODES))
+        );
+    }
+
+    #[test]
+    fn test_unique_solution() {
+        // x + y = 3, x - y = 1 needs no branching
+        let a = Matrix::from_i64_rows(&[&[1, 1], &[1, -1]]);
+        let (b, c) = (vector(&[3, 1]), vector(&[2, 1]));
+        let solution = IntegerProgram::new(&a, &b, &c).minimize().unwrap().unwrap();
+        assert_eq!(solution.x, vec![2, 1]);
+        assert_eq!(solution.objective, Rational::from(5i64));
+
+        // x - y = 3, x + y = 1 only has a negative solution
+        let (a, b) = (Matrix::from_i64_rows(&[&[1, -1], &[1, 1]]), vector(&[3, 1]));
+        assert_eq!(IntegerProgram::new(&a, &b, &c).minimize(), Ok(None));
+    }
+
+    #[test]
+    fn test_minimum_presses() {
+        // First machine of the day 10 example: 10 presses at best
+        let a = Matrix::from_i64_rows(&[
+            &[0, 0, 0, 0, 1, 1],
+            &[0, 1, 0, 0, 0, 1],
+            &[0, 0, 1, 1, 1, 0],
+            &[1, 1, 0, 1, 0, 0],
+        ]);
+        let (b, c) = (vector(&[3, 5, 4, 7]), vector(&[1; 6]));
+        let solution = IntegerProgram::new(&a, &b, &c).minimize().unwrap().unwrap();
+        assert_eq!(solution.objective, Rational::from(10i64));
+        for (i, &target) in b.iter().enumerate() {
+            let reached: i128 = (0..6).map(|j| a[(i, j)].numer() * solution.x[j]).sum();
+            assert_eq!(Rational::from(reached), target);
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use std::{cmp::Reverse, collections::BinaryHeap, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    SquaredEuclidean,
+    Manhattan,
+    Chebyshev,
+}
+
+impl Metric {
+    /// Distance between `a` and `b`, or `None` if it does not fit in an `i128`. Differences
+    /// along an axis always fit, so only the squares and sums need checking.
+    pub fn checked_distance<const K: usize>(&self, a: &[i64; K], b: &[i64; K]) -> Option<i128> {
+        let mut diffs = (0..K).map(|axis| (a[axis] as i128 - b[axis] as i128).abs());
+        match self {
+            Metric::SquaredEuclidean => {
+                diffs.try_fold(0i128, |acc, d| acc.checked_add(d.checked_mul(d)?))
+            },
+            Metric::Manhattan => diffs.try_fold(0i128, |acc, d| acc.checked_add(d)),
+            Metric::Chebyshev => Some(diffs.max().unwrap_or(0)),
+        }
+    }
+
+    pub fn distance<const K: usize>(&self, a: &[i64; K], b: &[i64; K]) -> i128 {
+        self.checked_distance(a, b).expect("Distance overflows an i128")
+    }
+
+    /// Lower bound on the distance to any point lying `diff` away along a single axis.
+    fn axis_distance(&self, diff: i128) -> i128 {
+        match self {
+            Metric::SquaredEuclidean => diff.saturating_mul(diff),
+            Metric::Manhattan | Metric::Chebyshev => diff.abs(),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "euclidean" => Ok(Metric::SquaredEuclidean),
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            _ => Err("Unknown metric, expected euclidean, manhattan or chebyshev".to_string()),
+        }
+    }
+}
+
+/// Static k-d tree over a slice of points, stored implicitly: within `order[lo..hi]` the median
+/// `order[(lo + hi) / 2]` splits the rest along axis `depth % K`.
+pub struct KdTree<'a, const K: usize> {
+    points: &'a [[i64; K]],
+    metric: Metric,
+    order: Vec<usize>,
+    /// Bounding box `(min, max)` of the subtree rooted at each node.
+    boxes: Vec<([i64; K], [i64; K])>,
+}
+
+impl<'a, const K: usize> KdTree<'a, K> {
+    pub fn new(points: &'a [[i64; K]], metric: Metric) -> Self {
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        Self::build(points, &mut order, 0);
+        let mut tree =
+            KdTree { points, metric, order, boxes: vec![([0; K], [0; K]); points.len()] };
+        if !points.is_empty() {
+            tree.bound_subtrees(0, points.len());
+        }
+        tree
+    }
+
+    fn bound_subtrees(&mut self, lo: usize, hi: usize) -> ([i64; K], [i64; K]) {
+        let mid = (lo + hi) / 2;
+        let point = self.points[self.order[mid]];
+        let (mut min, mut max) = (point, point);
+        for (sub_lo, sub_hi) in [(lo, mid), (mid + 1, hi)] {
+            if sub_lo < sub_hi {
+                let (sub_min, sub_max) = self.bound_subtrees(sub_lo, sub_hi);
+                for axis in 0..K {
+                    min[axis] = min[axis].min(sub_min[axis]);
+                    max[axis] = max[axis].max(sub_max[axis]);
+                }
+            }
+        }
+        self.boxes[mid] = (min, max);
+        (min, max)
+    }
+
+    /// Lower bound on the distance from `query` to any point of the subtree `order[lo..hi]`:
+    /// the distance to the closest point of its bounding box.
+    fn box_distance(&self, query: &[i64; K], lo: usize, hi: usize) -> i128 {
+        let (min, max) = &self.boxes[(lo + hi) / 2];
+        let closest: [i64; K] = std::array::from_fn(|axis| query[axis].clamp(min[axis], max[axis]));
+        self.metric.distance(query, &closest)
+    }
+
+    fn build(points: &[[i64; K]], order: &mut [usize], depth: usize) {
+        if order.len() <= 1 {
+            return;
+        }
+        let mid = order.len() / 2;
+        let axis = depth % K;
+        order.select_nth_unstable_by_key(mid, |&idx| points[idx][axis]);
+        let (left, right) = order.split_at_mut(mid);
+        Self::build(points, left, depth + 1);
+        Self::build(points, &mut right[1..], depth + 1);
+    }
+
+    pub fn points(&self) -> &'a [[i64; K]] {
+        self.points
+    }
+
+    /// The `k` points closest to `query` (itself included if present) as `(distance, index)`,
+    /// ordered by distance then index so that ties are broken deterministically.
+    pub fn nearest(&self, query: &[i64; K], k: usize) -> Vec<(i128, usize)> {
+        let mut best = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.nearest_in(query, k, 0, self.order.len(), 0, &mut best);
+        }
+        best.into_sorted_vec()
+    }
+
+    fn nearest_in(
+        &self,
+        query: &[i64; K],
+        k: usize,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        best: &mut BinaryHeap<(i128, usize)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        let idx = self.order[mid];
+        let point = &self.points[idx];
+
+        best.push((self.metric.distance(query, point), idx));
+        if best.len() > k {
+            best.pop();
+        }
+
+        let axis = depth % K;
+        let diff = query[axis] as i128 - point[axis] as i128;
+        let (near, far) =
+            if diff < 0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+
+        self.nearest_in(query, k, near.0, near.1, depth + 1, best);
+        // Equal distances must still be visited, as a smaller index wins the tie
+        let worst = best.peek().map_or(i128::MAX, |&(d, _)| d);
+        if best.len() < k || self.metric.axis_distance(diff) <= worst {
+            self.nearest_in(query, k, far.0, far.1, depth + 1, best);
+        }
+    }
+
+    /// Groups the points by `labels` (one per point) for `nearest_outside` queries.
+    pub fn with_labels<'t>(&'t self, labels: &'t [usize]) -> LabelledKdTree<'t, 'a, K> {
+        assert_eq!(labels.len(), self.points.len(), "Expected one label per point");
+        let mut uniform = vec![None; self.order.len()];
+        if !self.order.is_empty() {
+            self.label_subtrees(labels, 0, self.order.len(), &mut uniform);
+        }
+        LabelledKdTree { tree: self, labels, uniform }
+    }
+
+    /// Records at each node the label shared by its whole subtree `order[lo..hi]`, if any.
+    fn label_subtrees(
+        &self,
+        labels: &[usize],
+        lo: usize,
+        hi: usize,
+        uniform: &mut [Option<usize>],
+    ) -> Option<usize> {
+        let mid = (lo + hi) / 2;
+        let label = labels[self.order[mid]];
+        let left =
+            if lo < mid { self.label_subtrees(labels, lo, mid, uniform) } else { Some(label) };
+        let right = if mid + 1 < hi {
+            self.label_subtrees(labels, mid + 1, hi, uniform)
+        } else {
+            Some(label)
+        };
+        uniform[mid] = (left == Some(label) && right == Some(label)).then_some(label);
+        uniform[mid]
+    }
+
+    /// Indices of every point within `radius` (inclusive, in the tree's metric) of `query`.
+    /// No puzzle runs radius queries yet; only the tests do.
+    #[allow(dead_code)]
+    pub fn within_radius(&self, query: &[i64; K], radius: i128) -> Vec<usize> {
+        let mut found = Vec::new();
+        self.within_radius_in(query, radius, 0, self.order.len(), 0, &mut found);
+        found.sort_unstable();
+        found
+    }
+
+    #[allow(dead_code)]
+    fn within_radius_in(
+        &self,
+        query: &[i64; K],
+        radius: i128,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        found: &mut Vec<usize>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        let idx = self.order[mid];
+        let point = &self.points[idx];
+
+        if self.metric.distance(query, point) <= radius {
+            found.push(idx);
+        }
+
+        let diff = query[depth % K] as i128 - point[depth % K] as i128;
+        let reachable = self.metric.axis_distance(diff) <= radius;
+        if diff <= 0 || reachable {
+            self.within_radius_in(query, radius, lo, mid, depth + 1, found);
+        }
+        if diff >= 0 || reachable {
+            self.within_radius_in(query, radius, mid + 1, hi, depth + 1, found);
+        }
+    }
+}
+
+/// A k-d tree whose points carry labels, such as the circuit each junction box belongs to.
+/// Every node knows whether its whole subtree shares one label, so that queries for the
+/// nearest point with another label skip the subtrees of the query's own group.
+pub struct LabelledKdTree<'t, 'a, const K: usize> {
+    tree: &'t KdTree<'a, K>,
+    labels: &'t [usize],
+    uniform: Vec<Option<usize>>,
+}
+
+impl<const K: usize> LabelledKdTree<'_, '_, K> {
+    /// The point closest to point `i` among those labelled differently, as `(distance, index)`
+    /// with ties broken by the smaller index, or `None` if none lies within `bound`.
+    pub fn nearest_outside(&self, i: usize, bound: i128) -> Option<(i128, usize)> {
+        let mut best = (bound, usize::MAX);
+        self.nearest_outside_in(i, 0, self.tree.order.len(), 0, &mut best);
+        (best.1 != usize::MAX).then_some(best)
+    }
+
+    fn nearest_outside_in(
+        &self,
+        i: usize,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        best: &mut (i128, usize),
+    ) {
+        let label = self.labels[i];
+        let query = &self.tree.points[i];
+        // Equal distances must still be visited, as a smaller index wins the tie
+        if lo >= hi
+            || self.uniform[(lo + hi) / 2] == Some(label)
+            || self.tree.box_distance(query, lo, hi) > best.0
+        {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        let idx = self.tree.order[mid];
+        let point = &self.tree.points[idx];
+
+        if self.labels[idx] != label {
+            *best = (*best).min((self.tree.metric.distance(query, point), idx));
+        }
+
+        let axis = depth % K;
+        let diff = query[axis] as i128 - point[axis] as i128;
+        let (near, far) =
+            if diff < 0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+
+        self.nearest_outside_in(i, near.0, near.1, depth + 1, best);
+        self.nearest_outside_in(i, far.0, far.1, depth + 1, best);
+    }
+}
+
+/// Lazily yields every pair `(distance, i, j)` with `i < j`, by increasing distance then
+/// indices, without materializing all n²/2 pairs.
+///
+/// Each point keeps a cursor over its own neighbours (fetched from the tree in growing
+/// batches), and a heap holds the next candidate pair of every point.
+pub struct ClosestPairs<'a, const K: usize> {
+    tree: KdTree<'a, K>,
+    neighbors: Vec<Vec<(i128, usize)>>,
+    cursors: Vec<usize>,
+    heap: BinaryHeap<Reverse<(i128, usize, usize)>>,
+}
+
+impl<'a, const K: usize> ClosestPairs<'a, K> {
+    pub fn new(points: &'a [[i64; K]], metric: Metric) -> Self {
+        let n = points.len();
+        let mut pairs = ClosestPairs {
+            tree: KdTree::new(points, metric),
+            neighbors: vec![Vec::new(); n],
+            cursors: vec![0; n],
+            heap: BinaryHeap::new(),
+        };
+        for i in 0..n {
+            pairs.advance(i);
+        }
+        pairs
+    }
+
+    /// Pushes the next pair `(i, j)` with `j > i` from the neighbours of `i`, if any is left.
+    fn advance(&mut self, i: usize) {
+        let n = self.tree.points().len();
+        loop {
+            if self.cursors[i] == self.neighbors[i].len() {
+                let fetched = self.neighbors[i].len();
+                if fetched == n {
+                    return;
+                }
+                let k = (2 * fetched).max(8).min(n);
+                self.neighbors[i] = self.tree.nearest(&self.tree.points()[i], k);
+            }
+
+            let (d, j) = self.neighbors[i][self.cursors[i]];
+            self.cursors[i] += 1;
+            if j > i {
+                self.heap.push(Reverse((d, i, j)));
+                return;
+            }
+        }
+    }
+}
+
+impl<const K: usize> Iterator for ClosestPairs<'_, K> {
+    type Item = (i128, usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(pair) = self.heap.pop()?;
+        self.advance(pair.1);
+        Some(pair)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> Vec<[i64; 2]> {
+        (0..7).flat_map(|x| (0..5).map(move |y| [x * 3 % 7, y * y - x])).collect()
+    }
+
+    const METRICS: [Metric; 3] = [Metric::SquaredEuclidean, Metric::Manhattan, Metric::Chebyshev];
+
+    fn brute_force_pairs<const K: usize>(
+        points: &[[i64; K]],
+        metric: Metric,
+    ) -> Vec<(i128, usize, usize)> {
+        let mut pairs: Vec<_> = (0..points.len())
+            .flat_map(|i| {
+                (i + 1..points.len()).map(move |j| (metric.distance(&points[i], &points[j]), i, j))
+            })
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn test_nearest_matches_brute_force() {
+        let points = grid();
+        for metric in METRICS {
+            let tree = KdTree::new(&points, metric);
+            for query in [[0, 0], [3, 7], [-4, 20], [6, -6]] {
+                let mut expected: Vec<_> =
+                    (0..points.len()).map(|i| (metric.distance(&query, &points[i]), i)).collect();
+                expected.sort();
+                for k in [1, 4, 11, points.len()] {
+                    assert_eq!(tree.nearest(&query, k), expected[..k]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_within_radius() {
+        let points = grid();
+        let query = [2, 3];
+        for metric in METRICS {
+            let tree = KdTree::new(&points, metric);
+            let expected: Vec<usize> =
+                (0..points.len()).filter(|&i| metric.distance(&query, &points[i]) <= 4).collect();
+            assert_eq!(tree.within_radius(&query, 4), expected);
+        }
+    }
+
+    #[test]
+    fn test_nearest_outside_matches_brute_force() {
+        let points = grid();
+        let labels: Vec<usize> = (0..points.len()).map(|i| i * i % 7 / 3).collect();
+        for metric in METRICS {
+            let tree = KdTree::new(&points, metric);
+            let labelled = tree.with_labels(&labels);
+            for i in 0..points.len() {
+                let expected = (0..points.len())
+                    .filter(|&j| labels[j] != labels[i])
+                    .map(|j| (metric.distance(&points[i], &points[j]), j))
+                    .min();
+                assert_eq!(labelled.nearest_outside(i, i128::MAX), expected);
+                let (d, _) = expected.unwrap();
+                assert_eq!(labelled.nearest_outside(i, d - 1), None);
+            }
+        }
+
+        // A single label leaves nothing outside
+        let tree = KdTree::new(&points, Metric::Manhattan);
+        assert_eq!(tree.with_labels(&vec![3; points.len()]).nearest_outside(0, i128::MAX), None);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let (a, b) = ([1, -2, 3], [4, 2, 3]);
+        assert_eq!(Metric::SquaredEuclidean.distance(&a, &b), 25);
+        assert_eq!(Metric::Manhattan.distance(&a, &b), 7);
+        assert_eq!(Metric::Chebyshev.distance(&a, &b), 4);
+        assert_eq!("manhattan".parse(), Ok(Metric::Manhattan));
+        assert!("cosine".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn test_wide_coordinates() {
+        let (a, b) = ([i64::MAX, i64::MIN], [i64::MIN, i64::MAX]);
+        let span = u64::MAX as i128;
+        assert_eq!(Metric::Manhattan.distance(&a, &b), 2 * span);
+        assert_eq!(Metric::Chebyshev.distance(&a, &b), span);
+        assert_eq!(Metric::SquaredEuclidean.checked_distance(&a, &b), None);
+
+        let quarter = i64::MAX / 4;
+        let points = [[quarter, -quarter], [-quarter, quarter], [quarter - 1, -quarter]];
+        let pairs: Vec<_> = ClosestPairs::new(&points, Metric::SquaredEuclidean).collect();
+        assert_eq!(pairs, brute_force_pairs(&points, Metric::SquaredEuclidean));
+        assert_eq!(pairs[0], (1, 0, 2));
+        assert_eq!(pairs[2].0, 8 * (quarter as i128).pow(2));
+    }
+
+    #[test]
+    fn test_closest_pairs_in_order() {
+        let points = grid();
+        for metric in METRICS {
+            let lazy: Vec<_> = ClosestPairs::new(&points, metric).collect();
+            assert_eq!(lazy, brute_force_pairs(&points, metric));
+        }
+
+        let few = [[0, 0], [5, 5], [1, 1]];
+        let lazy: Vec<_> = ClosestPairs::new(&few, Metric::SquaredEuclidean).collect();
+        assert_eq!(lazy, brute_force_pairs(&few, Metric::SquaredEuclidean));
+    }
+
+    #[test]
+    fn test_closest_pairs_3d() {
+        let points: Vec<[i64; 3]> =
+            (0..40).map(|i: i64| [i * 37 % 11, i * i % 13, (i * 7) % 5 - 2]).collect();
+        for metric in METRICS {
+            let lazy: Vec<_> = ClosestPairs::new(&points, metric).take(100).collect();
+            assert_eq!(lazy, brute_force_pairs(&points, metric)[..100]);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::ops::{Index, IndexMut};
+
+use super::rational::Rational;
+
+/// Dense matrix over exact rationals, so elimination never depends on a tolerance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    data: Vec<Vec<Rational>>,
+    rows: usize,
+    cols: usize,
+}
+
+/// Every solution of `A·x = b`, written as `particular + Σ params[i] · basis[i]`.
+///
+/// Each basis vector belongs to one free column: it holds `1` at that column and `0` at every
+/// other free column, so the parameters are exactly the values of the free variables. The
+/// rank is the number of pivots, and the basis spans the null space of `A`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameterization {
+    pub particular: Vec<Rational>,
+    pub pivots: Vec<usize>,
+    pub free: Vec<usize>,
+    pub basis: Vec<Vec<Rational>>,
+}
+
+impl Matrix {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Matrix { data: vec![vec![Rational::ZERO; cols]; rows], rows, cols }
+    }
+
+    #[cfg(test)]
+    pub fn from_i64_rows(rows: &[&[i64]]) -> Self {
+        let data: Vec<Vec<Rational>> = rows.iter().map(|row| vector(row)).collect();
+        let cols = data.first().map_or(0, |row| row.len());
+        assert!(data.iter().all(|row| row.len() == cols), "Ragged matrix rows");
+        Matrix { data, rows: rows.len(), cols }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Parameterizes the solutions of `A·x = b`, or `None` if the system is inconsistent.
+    pub fn solve(&self, b: &[Rational]) -> Option<Parameterization> {
+        assert_eq!(b.len(), self.rows, "Right-hand side does not match the number of rows");
+
+        // Eliminate on the augmented matrix [A | b] without pivoting on the last column
+        let mut augmented = self.clone();
+        for (row, &value) in augmented.data.iter_mut().zip(b) {
+            row.push(value);
+        }
+        augmented.cols += 1;
+        let pivots = augmented.reduce_columns(self.cols);
+
+        // A non-zero right-hand side on an all-zero row means 0 = b
+        if augmented.data[pivots.len()..].iter().any(|row| !row[self.cols].is_zero()) {
+            return None;
+        }
+
+        let free: Vec<usize> = (0..self.cols).filter(|col| !pivots.contains(col)).collect();
+        let basis = augmented.basis(&pivots, &free);
+        let mut particular = vec![Rational::ZERO; self.cols];
+        for (row, &col) in pivots.iter().enumerate() {
+            particular[col] = augmented.data[row][self.cols];
+        }
+
+        Some(Parameterization { particular, pivots, free, basis })
+    }
+
+    /// Reduces the first `n_cols` columns to reduced row echelon form, returning the pivot
+    /// column of each non-zero row.
+    fn reduce_columns(&mut self, n_cols: usize) -> Vec<usize> {
+        let mut pivots = Vec::new();
+
+        for col in 0..n_cols {
+            let pivot = pivots.len();
+            let Some(best_row) = (pivot..self.rows).find(|&row| !self.data[row][col].is_zero())
+            else {
+                continue;
+            };
+
+            self.data.swap(pivot, best_row);
+            pivots.push(col);
+
+            // Normalize pivot row
+            let pivot_val = self.data[pivot][col];
+            for val in &mut self.data[pivot][col..] {
+                *val = *val / pivot_val;
+            }
+
+            // Eliminate column in all other rows
+            let pivot_row = self.data[pivot].clone();
+            for (row, data_row) in self.data.iter_mut().enumerate() {
+                let factor = data_row[col];
+                if row != pivot && !factor.is_zero() {
+                    for (val, &p) in data_row[col..].iter_mut().zip(&pivot_row[col..]) {
+                        *val = *val - factor * p;
+                    }
+                }
+            }
+
+            if pivots.len() == self.rows {
+                break;
+            }
+        }
+
+        pivots
+    }
+
+    fn basis(&self, pivots: &[usize], free: &[usize]) -> Vec<Vec<Rational>> {
+        free.iter()
+            .map(|&f| {
+                let mut vector = vec![Rational::ZERO; pivots.len() + free.len()];
+                vector[f] = Rational::ONE;
+                for (row, &col) in pivots.iter().enumerate() {
+                    vector[col] = -self.data[row][f];
+                }
+                vector
+            })
+            .collect()
+    }
+}
+
+/// Column vector of exact rationals, the right-hand side and cost shape used by the tests.
+#[cfg(test)]
+pub fn vector(values: &[i64]) -> Vec<Rational> {
+    values.iter().map(|&v| Rational::from(v)).collect()
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = Rational;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Rational {
+        &self.data[row][col]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Rational {
+        &mut self.data[row][col]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dependent_rows() {
+        // The third row is the difference of the others, so the rank is 2
+        let m = Matrix::from_i64_rows(&[&[2, 4, 6], &[1, 3, 5], &[1, 1, 1]]);
+        let solution = m.solve(&vector(&[0, 0, 0])).unwrap();
+        assert_eq!(solution.pivots, vec![0, 1]);
+        assert_eq!(solution.basis, vec![vector(&[1, -2, 1])]);
+    }
+
+    #[test]
+    fn test_null_space() {
+        let m = Matrix::from_i64_rows(&[&[1, 2, 3], &[2, 4, 6]]);
+        let basis = m.solve(&vector(&[0, 0])).unwrap().basis;
+        assert_eq!(basis, vec![vector(&[-2, 1, 0]), vector(&[-3, 0, 1])]);
+        for v in basis {
+            for row in 0..m.rows() {
+                let dot =
+                    (0..m.cols()).fold(Rational::ZERO, |acc, col| acc + m[(row, col)] * v[col]);
+                assert!(dot.is_zero());
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_exact_fractions() {
+        // 3x + y = 1, x - y = 0  =>  x = y = 1/4
+        let m = Matrix::from_i64_rows(&[&[3, 1], &[1, -1]]);
+        let solution = m.solve(&vector(&[1, 0])).unwrap();
+        assert!(solution.free.is_empty());
+        assert_eq!(solution.particular, vec![Rational::new(1, 4), Rational::new(1, 4)]);
+    }
+
+    #[test]
+    fn test_solve_parameterized() {
+        // x + y + z = 6, y - z = 1
+        let m = Matrix::from_i64_rows(&[&[1, 1, 1], &[0, 1, -1]]);
+        let solution = m.solve(&vector(&[6, 1])).unwrap();
+        assert_eq!(solution.pivots, vec![0, 1]);
+        assert_eq!(solution.free, vec![2]);
+        assert_eq!(solution.particular, vector(&[5, 1, 0]));
+        assert_eq!(solution.basis, vec![vector(&[-2, 1, 1])]);
+    }
+
+    #[test]
+    fn test_solve_inconsistent() {
+        let m = Matrix::from_i64_rows(&[&[1, 1], &[2, 2]]);
+        assert_eq!(m.solve(&vector(&[1, 3])), None);
+    }
+
+    #[test]
+    fn test_large_coefficients() {
+        // Values that would lose precision as f64
+        let big = 1i64 << 55;
+        let m = Matrix::from_i64_rows(&[&[big, 1], &[1, 0]]);
+        let solution = m.solve(&vector(&[big + 3, 1])).unwrap();
+        assert_eq!(solution.particular, vector(&[1, 3]));
+    }
+}
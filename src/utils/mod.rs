@@ -0,0 +1,10 @@
+pub mod bigint;
+pub mod bitmask;
+pub mod cli;
+pub mod ilp;
+pub mod kdtree;
+pub mod matrix;
+pub mod rational;
+#[cfg(test)]
+pub mod rng;
+pub mod simplex;
@@ -0,0 +1,17 @@
+/// Seeded linear congruential generator, so randomized tests stay reproducible without a
+/// dependency.
+pub struct Lcg {
+    state: u32,
+}
+
+impl Lcg {
+    pub fn new(seed: u32) -> Self {
+        Lcg { state: seed }
+    }
+
+    /// Next value in `0..bound`, taken from the high bits, which have the longest period.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        self.state = self.state.wrapping_mul(1103515245).wrapping_add(12345);
+        (self.state >> 16) % bound
+    }
+}
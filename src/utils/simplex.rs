@@ -0,0 +1,189 @@
+use super::{matrix::Matrix, rational::Rational};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LpSolution {
+    pub x: Vec<Rational>,
+    pub objective: Rational,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LpOutcome {
+    Optimal(LpSolution),
+    Infeasible,
+    Unbounded,
+}
+
+/// Dense simplex tableau: one row per constraint plus the reduced-cost row, the last column
+/// holding the right-hand side.
+struct Tableau {
+    rows: Vec<Vec<Rational>>,
+    objective: Vec<Rational>,
+    basis: Vec<usize>,
+}
+
+impl Tableau {
+    fn rhs(&self) -> usize {
+        self.objective.len() - 1
+    }
+
+    fn pivot(&mut self, pivot_row: usize, col: usize) {
+        let pivot_val = self.rows[pivot_row][col];
+        for val in &mut self.rows[pivot_row] {
+            *val = *val / pivot_val;
+        }
+
+        let pivot = self.rows[pivot_row].clone();
+        let others = self
+            .rows
+            .iter_mut()
+            .enumerate()
+            .filter(|(row, _)| *row != pivot_row)
+            .map(|(_, data)| data)
+            .chain(std::iter::once(&mut self.objective));
+        for data in others {
+            let factor = data[col];
+            if !factor.is_zero() {
+                for (val, &p) in data.iter_mut().zip(&pivot) {
+                    *val = *val - factor * p;
+                }
+            }
+        }
+
+        self.basis[pivot_row] = col;
+    }
+
+    /// Recomputes the reduced costs of `cost` for the current basis.
+    fn price(&mut self, cost: &[Rational]) {
+        self.objective.fill(Rational::ZERO);
+        self.objective[..cost.len()].copy_from_slice(cost);
+        for (row, &basic) in self.rows.iter().zip(&self.basis) {
+            let weight = cost.get(basic).copied().unwrap_or(Rational::ZERO);
+            if !weight.is_zero() {
+                for (val, &v) in self.objective.iter_mut().zip(row) {
+                    *val = *val - weight * v;
+                }
+            }
+        }
+    }
+
+    /// Runs simplex iterations over the first `n_cols` columns, using Bland's rule so that
+    /// degenerate pivots never cycle. Returns `false` if the objective is unbounded.
+    fn optimize(&mut self, n_cols: usize) -> bool {
+        let rhs = self.rhs();
+        while let Some(col) = (0..n_cols).find(|&col| self.objective[col] < Rational::ZERO) {
+            let leaving = self
+                .rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| row[col] > Rational::ZERO)
+                .map(|(i, row)| (row[rhs] / row[col], self.basis[i], i))
+                .min();
+
+            match leaving {
+                Some((_, _, row)) => self.pivot(row, col),
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Minimizes `c·x` subject to `A·x = b` and `x ≥ 0` with the two-phase simplex method.
+pub fn minimize(a: &Matrix, b: &[Rational], c: &[Rational]) -> LpOutcome {
+    let (m, n) = (a.rows(), a.cols());
+    assert_eq!(b.len(), m, "Right-hand side does not match the number of rows");
+    assert_eq!(c.len(), n, "Cost vector does not match the number of columns");
+
+    // Phase 1: one artificial variable per row, starting from the basis they form
+    let rows = (0..m)
+        .map(|i| {
+            let sign = if b[i] < Rational::ZERO { -Rational::ONE } else { Rational::ONE };
+            let mut row: Vec<Rational> = (0..n).map(|j| sign * a[(i, j)]).collect();
+            row.extend((0..m).map(|k| if k == i { Rational::ONE } else { Rational::ZERO }));
+            row.push(sign * b[i]);
+            row
+        })
+        .collect();
+    let mut tableau =
+        Tableau { rows, objective: vec![Rational::ZERO; n + m + 1], basis: (n..n + m).collect() };
+    let artificial_cost: Vec<Rational> =
+        (0..n + m).map(|j| if j < n { Rational::ZERO } else { Rational::ONE }).collect();
+    tableau.price(&artificial_cost);
+    tableau.optimize(n + m);
+
+    let rhs = tableau.rhs();
+    if !tableau.objective[rhs].is_zero() {
+        return LpOutcome::Infeasible;
+    }
+
+    // Drive the remaining (zero-valued) artificial variables out of the basis, dropping the
+    // rows that turn out to be redundant
+    let mut row = 0;
+    while row < tableau.rows.len() {
+        if tableau.basis[row] < n {
+            row += 1;
+        } else if let Some(col) = (0..n).find(|&col| !tableau.rows[row][col].is_zero()) {
+            tableau.pivot(row, col);
+            row += 1;
+        } else {
+            tableau.rows.remove(row);
+            tableau.basis.remove(row);
+        }
+    }
+    for data in &mut tableau.rows {
+        data.drain(n..n + m);
+    }
+
+    // Phase 2: optimize the real objective from the feasible basis
+    tableau.objective = vec![Rational::ZERO; n + 1];
+    tableau.price(c);
+    if !tableau.optimize(n) {
+        return LpOutcome::Unbounded;
+    }
+
+    let mut x = vec![Rational::ZERO; n];
+    for (data, &basic) in tableau.rows.iter().zip(&tableau.basis) {
+        x[basic] = data[n];
+    }
+    LpOutcome::Optimal(LpSolution { x, objective: -tableau.objective[n] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::matrix::vector;
+
+    #[test]
+    fn test_fractional_optimum() {
+        // min -x - y  s.t.  2x + y + s1 = 4, x + 2y + s2 = 4  =>  x = y = 4/3
+        let a = Matrix::from_i64_rows(&[&[2, 1, 1, 0], &[1, 2, 0, 1]]);
+        let LpOutcome::Optimal(solution) = minimize(&a, &vector(&[4, 4]), &vector(&[-1, -1, 0, 0]))
+        else {
+            panic!("Expected an optimal solution");
+        };
+        assert_eq!(solution.objective, Rational::new(-8, 3));
+        assert_eq!(solution.x[..2], [Rational::new(4, 3), Rational::new(4, 3)]);
+    }
+
+    #[test]
+    fn test_redundant_rows() {
+        let a = Matrix::from_i64_rows(&[&[1, 1], &[2, 2]]);
+        let outcome = minimize(&a, &vector(&[3, 6]), &vector(&[1, 2]));
+        assert_eq!(
+            outcome,
+            LpOutcome::Optimal(LpSolution { x: vector(&[3, 0]), objective: Rational::from(3i64) })
+        );
+    }
+
+    #[test]
+    fn test_infeasible() {
+        let a = Matrix::from_i64_rows(&[&[1, 1]]);
+        assert_eq!(minimize(&a, &vector(&[-1]), &vector(&[1, 1])), LpOutcome::Infeasible);
+    }
+
+    #[test]
+    fn test_unbounded() {
+        let a = Matrix::from_i64_rows(&[&[1, -1]]);
+        assert_eq!(minimize(&a, &vector(&[1]), &vector(&[0, -1])), LpOutcome::Unbounded);
+    }
+}